@@ -27,7 +27,92 @@ pub async fn set_migration(value: i32, db: &Client) -> Result<(), Error> {
     Ok(())
 }
 
-pub async fn clear(db: &Client) -> Result<(), Error> {
+/// Runs the queries of a migration.
+///
+/// The queries are run together, except the ones that build or drop an index concurrently, that
+/// can't run inside a transaction block and are run on their own.
+pub async fn run(sql: &str, db: &Client) -> Result<(), Error> {
+    let mut batch = String::new();
+
+    for statement in statements(sql) {
+        if statement.to_uppercase().contains("INDEX CONCURRENTLY") {
+            if !batch.trim().is_empty() {
+                db.simple_query(&batch as &str).await?;
+            }
+            batch.clear();
+            db.simple_query(&statement as &str).await?;
+        } else {
+            batch.push_str(&statement);
+        }
+    }
+
+    if !batch.trim().is_empty() {
+        db.simple_query(&batch as &str).await?;
+    }
+
+    Ok(())
+}
+
+/// Splits some SQL into statements, ignoring the semicolons in strings, identifiers, dollar quoted
+/// bodies and comments.
+pub fn statements(sql: &str) -> Vec<String> {
+    let mut output = vec![];
+    let mut current = String::new();
+    let mut rest = sql;
+
+    while let Some(c) = rest.chars().next() {
+        let end = match c {
+            '\'' | '"' => rest[1..].find(c).map(|i| i + 2),
+            '$' => rest[1..].find('$').and_then(|i| {
+                let tag = &rest[..i + 2];
+                rest[i + 2..].find(tag).map(|j| i + 2 + j + tag.len())
+            }),
+            '-' if rest.starts_with("--") => rest.find('\n').map(|i| i + 1),
+            '/' if rest.starts_with("/*") => rest.find("*/").map(|i| i + 2),
+            _ => Some(c.len_utf8()),
+        }
+        .unwrap_or(rest.len());
+
+        current.push_str(&rest[..end]);
+        rest = &rest[end..];
+
+        if c == ';' {
+            output.push(current.clone());
+            current.clear();
+        }
+    }
+
+    if !current.trim().is_empty() {
+        output.push(current);
+    }
+
+    output
+}
+
+pub async fn schemas(pattern: &str, db: &Client) -> Result<Vec<String>, Error> {
+    let rows = db
+        .query(
+            "SELECT nspname FROM pg_namespace WHERE nspname LIKE $1 ORDER BY nspname;",
+            &[&pattern],
+        )
+        .await?;
+    Ok(rows.into_iter().map(|x| x.get(0)).collect())
+}
+
+pub async fn set_search_path(schema: &str, db: &Client) -> Result<(), Error> {
+    // Only the schema is in the search path, so that each schema gets its own ergol table.
+    let query = format!("SET search_path TO \"{}\";", schema.replace('"', "\"\""));
+    db.query(&query as &str, &[]).await?;
+    Ok(())
+}
+
+pub async fn clear(schemas: &[String], db: &Client) -> Result<(), Error> {
+    // Clear schemas
+    for schema in schemas {
+        let query = format!("DROP SCHEMA IF EXISTS \"{}\" CASCADE;", schema);
+        db.query(&query as &str, &[]).await?;
+    }
+
     // Clear tables
     db.query(
         r#"
@@ -1,9 +1,9 @@
 //! This module contains everything needed to compute diffs between databases.
 
-use ergol_core::{Column, Element, Enum, Table};
+use ergol_core::{schemas, Column, Constraint, Domain, Element, Enum, Index, Schema, Table, Ty};
 
 /// A state of db containing types and tables.
-pub type State = (Vec<Enum>, Vec<Table>);
+pub type State = (Vec<Enum>, Vec<Domain>, Vec<Table>);
 
 /// A unit of diff between db states.
 #[derive(Clone, Debug)]
@@ -14,12 +14,41 @@ pub enum DiffElement {
     /// An element needs to be dropped.
     Drop(Element),
 
-    /// Creates a new column in a table.
+    /// Creates a new column in a table, whose qualified name is the first field.
     CreateColumn(String, Column),
 
-    /// Drops a column in a table.
+    /// Drops a column in a table, whose qualified name is the first field.
     DropColumn(String, Column),
 
+    /// Changes the referential actions of a foreign key column, from the third field to the fourth
+    /// field, in a table whose qualified name and name are the first fields.
+    AlterForeignKey(String, String, Column, Column),
+
+    /// Changes the type of a column whose values can be converted, from the second field to the
+    /// third field, in a table whose qualified name is the first field.
+    AlterType(String, Column, Column),
+
+    /// Changes the columns of the primary key, from the third field to the fourth field, in a
+    /// table whose qualified name and name are the first fields.
+    AlterPrimaryKey(String, String, Vec<String>, Vec<String>),
+
+    /// Adds a constraint to a table, whose qualified name is the first field.
+    CreateConstraint(String, Constraint),
+
+    /// Drops a constraint from a table, whose qualified name is the first field.
+    DropConstraint(String, Constraint),
+
+    /// Creates an index concurrently on a table, whose schema and qualified name are the first
+    /// fields.
+    CreateIndex(Option<String>, String, Index),
+
+    /// Drops an index concurrently from a table, whose schema and qualified name are the first
+    /// fields.
+    DropIndex(Option<String>, String, Index),
+
+    /// Changes the check constraint of a domain, from the first field to the second field.
+    AlterDomainCheck(Domain, Domain),
+
     /// Creates a variant in an enum.
     CreateVariant(String, String),
 
@@ -33,16 +62,79 @@ impl DiffElement {
         match self {
             DiffElement::Create(e) => e.create(),
             DiffElement::Drop(e) => e.drop(),
-            DiffElement::CreateColumn(t, c) => {
-                format!(
-                    "ALTER TABLE \"{}\" ADD \"{}\" {} DEFAULT /* TODO default value */;",
+            DiffElement::CreateColumn(t, c) => match (&c.default, &c.generated) {
+                (None, None) => format!(
+                    "ALTER TABLE {} ADD \"{}\" {}{} DEFAULT /* TODO default value */;",
                     t,
                     c.name,
                     c.ty.to_postgres(),
+                    c.actions(),
+                ),
+                _ => format!("ALTER TABLE {} ADD {};", t, c.to_postgres()),
+            },
+            DiffElement::DropColumn(t, c) => {
+                format!("ALTER TABLE {} DROP COLUMN \"{}\";", t, c.name)
+            }
+            DiffElement::AlterForeignKey(t, name, _, c) => match c.ty.foreign_key() {
+                Some(f) => format!(
+                    "ALTER TABLE {0} DROP CONSTRAINT \"{1}_{2}_fkey\", ADD CONSTRAINT \"{1}_{2}_fkey\" FOREIGN KEY (\"{2}\") {3}{4};",
+                    t,
+                    name,
+                    c.name,
+                    f.references(),
+                    c.actions(),
+                ),
+                None => String::new(),
+            },
+            DiffElement::AlterType(t, before, after) => {
+                let using = conversion(&before.ty, &after.ty, &after.name).unwrap_or_default();
+
+                format!(
+                    "ALTER TABLE {} ALTER COLUMN \"{}\" TYPE {} USING {};",
+                    t,
+                    after.name,
+                    Ty::Option(Box::new(after.ty.inner().clone())).to_postgres(),
+                    using,
                 )
             }
-            DiffElement::DropColumn(t, c) => {
-                format!("ALTER TABLE \"{}\" DROP COLUMN \"{}\";", t, c.name)
+            DiffElement::AlterPrimaryKey(t, name, _, after) => format!(
+                "ALTER TABLE {} DROP CONSTRAINT IF EXISTS \"{}_pkey\", ADD PRIMARY KEY ({});",
+                t,
+                name,
+                after
+                    .iter()
+                    .map(|x| format!("\"{}\"", x))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ),
+            DiffElement::CreateConstraint(t, c) => {
+                format!("ALTER TABLE {} ADD {};", t, c.to_postgres())
+            }
+            DiffElement::DropConstraint(t, c) => {
+                format!("ALTER TABLE {} DROP CONSTRAINT \"{}\";", t, c.name)
+            }
+            DiffElement::CreateIndex(_, t, i) => i.create_index(t, true),
+            DiffElement::DropIndex(s, _, i) => i.drop_index(s.as_deref(), true),
+            DiffElement::AlterDomainCheck(before, after) => {
+                let mut queries = vec![];
+
+                if before.check.is_some() {
+                    queries.push(format!(
+                        "ALTER DOMAIN \"{}\" DROP CONSTRAINT \"{}\";",
+                        before.name,
+                        before.check_name()
+                    ));
+                }
+
+                if let Some(check) = &after.check {
+                    queries.push(format!(
+                        "ALTER DOMAIN \"{}\" ADD {};",
+                        after.name,
+                        after.check_constraint(check)
+                    ));
+                }
+
+                queries.join("\n")
             }
             DiffElement::CreateVariant(t, v) => format!("ALTER TYPE \"{}\" ADD VALUE '{}';", t, v),
             DiffElement::DropVariant(t, v) => format!("ALTER TYPE \"{}\" DROP VALUE '{}';", t, v),
@@ -56,6 +148,32 @@ impl DiffElement {
             DiffElement::Drop(e) => DiffElement::Create(e.clone()).hint(),
             DiffElement::CreateColumn(c, t) => DiffElement::DropColumn(c.clone(), t.clone()).hint(),
             DiffElement::DropColumn(c, t) => DiffElement::CreateColumn(c.clone(), t.clone()).hint(),
+            DiffElement::AlterForeignKey(t, name, before, after) => {
+                DiffElement::AlterForeignKey(t.clone(), name.clone(), after.clone(), before.clone())
+                    .hint()
+            }
+            DiffElement::AlterType(t, before, after) => {
+                DiffElement::AlterType(t.clone(), after.clone(), before.clone()).hint()
+            }
+            DiffElement::AlterPrimaryKey(t, name, before, after) => {
+                DiffElement::AlterPrimaryKey(t.clone(), name.clone(), after.clone(), before.clone())
+                    .hint()
+            }
+            DiffElement::CreateConstraint(t, c) => {
+                DiffElement::DropConstraint(t.clone(), c.clone()).hint()
+            }
+            DiffElement::DropConstraint(t, c) => {
+                DiffElement::CreateConstraint(t.clone(), c.clone()).hint()
+            }
+            DiffElement::CreateIndex(s, t, i) => {
+                DiffElement::DropIndex(s.clone(), t.clone(), i.clone()).hint()
+            }
+            DiffElement::DropIndex(s, t, i) => {
+                DiffElement::CreateIndex(s.clone(), t.clone(), i.clone()).hint()
+            }
+            DiffElement::AlterDomainCheck(before, after) => {
+                DiffElement::AlterDomainCheck(after.clone(), before.clone()).hint()
+            }
             DiffElement::CreateVariant(t, v) => {
                 DiffElement::DropVariant(t.clone(), v.clone()).hint()
             }
@@ -81,9 +199,13 @@ impl Diff {
     }
 
     /// Returns a hint of the revert migration request.
+    ///
+    /// The elements are reverted in reverse order, so that schemas are dropped after their
+    /// tables.
     pub fn hint_revert(&self) -> String {
         self.0
             .iter()
+            .rev()
             .map(DiffElement::hint_revert)
             .collect::<Vec<_>>()
             .join("\n")
@@ -96,9 +218,23 @@ impl Diff {
 }
 
 /// Computes the diff between two states.
-pub fn diff((before_enums, before_tables): State, (after_enums, after_tables): State) -> Diff {
+pub fn diff(
+    (before_enums, before_domains, before_tables): State,
+    (after_enums, after_domains, after_tables): State,
+) -> Diff {
     let mut vec = vec![];
 
+    let before_schemas = schemas(&before_tables);
+    let after_schemas = schemas(&after_tables);
+
+    for s in &after_schemas {
+        if !before_schemas.contains(s) {
+            vec.push(DiffElement::Create(Element::Schema(Schema {
+                name: s.clone(),
+            })));
+        }
+    }
+
     for e in &before_enums {
         match after_enums.iter().find(|x| x.name == e.name) {
             None => vec.push(DiffElement::Drop(Element::Enum(e.clone()))),
@@ -113,8 +249,33 @@ pub fn diff((before_enums, before_tables): State, (after_enums, after_tables): S
         }
     }
 
+    // A domain can not change its base type, so a domain whose type changed is dropped and created
+    // again.
+    for d in &before_domains {
+        match after_domains.iter().find(|x| x.name == d.name) {
+            None => vec.push(DiffElement::Drop(Element::Domain(d.clone()))),
+            Some(x) if x.ty == d.ty && x.check != d.check => {
+                vec.push(DiffElement::AlterDomainCheck(d.clone(), x.clone()));
+            }
+            Some(x) if x != d => {
+                vec.push(DiffElement::Drop(Element::Domain(d.clone())));
+                vec.push(DiffElement::Create(Element::Domain(x.clone())));
+            }
+            _ => (),
+        }
+    }
+
+    for d in after_domains {
+        if before_domains.iter().find(|x| x.name == d.name).is_none() {
+            vec.push(DiffElement::Create(Element::Domain(d)));
+        }
+    }
+
     for e in &before_tables {
-        match after_tables.iter().find(|x| x.name == e.name) {
+        match after_tables
+            .iter()
+            .find(|x| x.name == e.name && x.schema == e.schema)
+        {
             None => vec.push(DiffElement::Drop(Element::Table(e.clone()))),
             Some(x) if x != e => vec.append(&mut diff_table(e, x)),
             _ => (),
@@ -122,11 +283,21 @@ pub fn diff((before_enums, before_tables): State, (after_enums, after_tables): S
     }
 
     for e in after_tables {
-        if before_tables.iter().find(|x| x.name == e.name).is_none() {
+        if before_tables
+            .iter()
+            .find(|x| x.name == e.name && x.schema == e.schema)
+            .is_none()
+        {
             vec.push(DiffElement::Create(Element::Table(e)));
         }
     }
 
+    for s in before_schemas {
+        if !after_schemas.contains(&s) {
+            vec.push(DiffElement::Drop(Element::Schema(Schema { name: s })));
+        }
+    }
+
     Diff(vec)
 }
 
@@ -134,31 +305,170 @@ pub fn diff((before_enums, before_tables): State, (after_enums, after_tables): S
 pub fn diff_table(before: &Table, after: &Table) -> Vec<DiffElement> {
     let mut vec = vec![];
 
+    // The generated columns are the last columns of a table, so that the rows are read with the
+    // same positions: they are created again after the new columns, and when they change.
+    let new_columns = after
+        .columns
+        .iter()
+        .any(|c| c.generated.is_none() && !before.columns.iter().any(|x| x.name == c.name));
+
+    let regenerated = before
+        .columns
+        .iter()
+        .filter(|c| c.generated.is_some())
+        .filter(|c| match after.columns.iter().find(|x| x.name == c.name) {
+            Some(c2) => new_columns || c != &c2,
+            None => false,
+        })
+        .map(|c| c.name.clone())
+        .collect::<Vec<_>>();
+
+    // The indexes of the columns that are created again are dropped with them.
+    let regenerated_index = |i: &Index| i.columns.iter().any(|x| regenerated.contains(x));
+
+    // The constraints and indexes are dropped before the columns they may use, and the ones that
+    // changed are dropped and created again.
+    for c in &before.constraints {
+        if !after.constraints.contains(c) {
+            vec.push(DiffElement::DropConstraint(
+                before.qualified_name(),
+                c.clone(),
+            ));
+        }
+    }
+
+    for i in &before.indexes {
+        if !after.indexes.contains(i) || regenerated_index(i) {
+            vec.push(DiffElement::DropIndex(
+                before.schema.clone(),
+                before.qualified_name(),
+                i.clone(),
+            ));
+        }
+    }
+
     for c in &before.columns {
         match after.columns.iter().find(|x| x.name == c.name) {
-            None => vec.push(DiffElement::DropColumn(before.name.clone(), c.clone())),
-            Some(c2) if c != c2 => eprintln!("should alter column"),
+            None => vec.push(DiffElement::DropColumn(before.qualified_name(), c.clone())),
+            Some(_) if regenerated.contains(&c.name) => {
+                vec.push(DiffElement::DropColumn(before.qualified_name(), c.clone()))
+            }
+            // A column that joins or leaves the primary key keeps its values, and the primary key
+            // is changed below.
+            Some(c2) if c != c2 && without_key(c) == without_key(c2) => (),
+            Some(c2) if c != c2 => {
+                let actions_only = Column {
+                    on_delete: c2.on_delete,
+                    on_update: c2.on_update,
+                    ..c.clone()
+                } == *c2;
+
+                let convertible = Column {
+                    ty: c2.ty.clone(),
+                    ..c.clone()
+                } == *c2
+                    && conversion(&c.ty, &c2.ty, &c.name).is_some();
+
+                if actions_only {
+                    vec.push(DiffElement::AlterForeignKey(
+                        before.qualified_name(),
+                        before.name.clone(),
+                        c.clone(),
+                        c2.clone(),
+                    ));
+                } else if convertible {
+                    vec.push(DiffElement::AlterType(
+                        before.qualified_name(),
+                        c.clone(),
+                        c2.clone(),
+                    ));
+                } else {
+                    eprintln!("should alter column");
+                }
+            }
             _ => (),
         }
     }
 
     for c in &after.columns {
-        if before.columns.iter().find(|x| x.name == c.name).is_none() {
-            vec.push(DiffElement::CreateColumn(before.name.clone(), c.clone()));
+        if before.columns.iter().find(|x| x.name == c.name).is_none()
+            || regenerated.contains(&c.name)
+        {
+            vec.push(DiffElement::CreateColumn(
+                before.qualified_name(),
+                c.clone(),
+            ));
+        }
+    }
+
+    // The primary key is changed after the creation of its new columns, and the columns of the
+    // old one that were dropped have already dropped it.
+    let (before_key, after_key) = (before.key_columns(), after.key_columns());
+    if before_key != after_key {
+        vec.push(DiffElement::AlterPrimaryKey(
+            before.qualified_name(),
+            before.name.clone(),
+            before_key,
+            after_key,
+        ));
+    }
+
+    for c in &after.constraints {
+        if !before.constraints.contains(c) {
+            vec.push(DiffElement::CreateConstraint(
+                before.qualified_name(),
+                c.clone(),
+            ));
+        }
+    }
+
+    for i in &after.indexes {
+        if !before.indexes.contains(i) || regenerated_index(i) {
+            vec.push(DiffElement::CreateIndex(
+                before.schema.clone(),
+                before.qualified_name(),
+                i.clone(),
+            ));
         }
     }
 
     vec
 }
 
+/// Returns the column with the type of its values, if it is the primary key of its table.
+fn without_key(column: &Column) -> Column {
+    Column {
+        ty: column.ty.key().unwrap_or_else(|| column.ty.clone()),
+        ..column.clone()
+    }
+}
+
+/// Returns the expression that converts the values of a column from a type to another, if the
+/// values can be converted.
+///
+/// The nullability of the column must be kept, and the values can become arrays of one element,
+/// the first element of their array, or change between json and jsonb.
+pub fn conversion(before: &Ty, after: &Ty, column: &str) -> Option<String> {
+    if matches!(before, Ty::Option(_)) != matches!(after, Ty::Option(_)) {
+        return None;
+    }
+
+    match (before.inner(), after.inner()) {
+        (ty, Ty::Array(inner)) if **inner == *ty => Some(format!("ARRAY[\"{}\"]", column)),
+        (Ty::Array(inner), ty) if **inner == *ty => Some(format!("\"{}\"[1]", column)),
+        (Ty::Json, Ty::Jsonb) => Some(format!("\"{}\"::jsonb", column)),
+        (Ty::Jsonb, Ty::Json) => Some(format!("\"{}\"::json", column)),
+        _ => None,
+    }
+}
+
 /// Computes the diff between two enums.
 pub fn diff_enum(before: &Enum, after: &Enum) -> Vec<DiffElement> {
     let mut vec = vec![];
 
     for c in &before.variants {
-        match after.variants.iter().find(|x| *x == c) {
-            None => vec.push(DiffElement::DropVariant(before.name.clone(), c.clone())),
-            _ => (),
+        if after.variants.iter().find(|x| *x == c).is_none() {
+            vec.push(DiffElement::DropVariant(before.name.clone(), c.clone()))
         }
     }
 
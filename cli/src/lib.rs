@@ -3,41 +3,17 @@ pub mod diff;
 
 use std::env::current_dir;
 use std::error::Error;
-use std::fs::{copy, create_dir, read_dir, read_to_string, File};
+use std::fs::{copy, create_dir, create_dir_all, read_dir, read_to_string, File};
 use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::process::Command;
 
 use toml::Value;
 
-use ergol_core::{Element, Table};
+use ergol_core::Element;
 
 use crate::diff::{diff, Diff, State};
 
-/// Tries to sort the tables in order to avoid problems with dependencies.
-pub fn order(tables: Vec<Table>) -> Vec<Table> {
-    let mut current: Vec<String> = vec![];
-    let mut output_tables = vec![];
-    let len = tables.len();
-
-    for _ in 0..len {
-        for table in &tables {
-            // Check dependencies
-            if !current.contains(&table.name)
-                && table.dependencies().iter().all(|x| current.contains(x))
-            {
-                current.push(table.name.clone());
-                output_tables.push(table.clone());
-            }
-        }
-    }
-
-    if output_tables.len() != len {
-        tables
-    } else {
-        output_tables
-    }
-}
-
 /// Find cargo toml.
 pub fn find_cargo_toml() -> Option<PathBuf> {
     let mut current = current_dir().ok()?;
@@ -62,7 +38,7 @@ pub fn last_saved_state<P: AsRef<Path>>(p: P) -> Result<(Option<u32>, State), Bo
         if !p.join(format!("{}", current)).is_dir() {
             if current == 0 {
                 // Last state is empty.
-                return Ok((None, (vec![], vec![])));
+                return Ok((None, (vec![], vec![], vec![])));
             } else {
                 return state_from_dir(p.join(format!("{}", current - 1)))
                     .map(|x| (Some(current - 1), x));
@@ -74,24 +50,33 @@ pub fn last_saved_state<P: AsRef<Path>>(p: P) -> Result<(Option<u32>, State), Bo
 }
 
 /// Returns the db state from a directory.
+///
+/// Each file contains the elements of the struct or the type that gives its name to the file.
 pub fn state_from_dir<P: AsRef<Path>>(path: P) -> Result<State, Box<dyn Error>> {
-    let mut tables = vec![];
-    let mut enums = vec![];
+    let mut entities = vec![];
 
     for file in read_dir(path.as_ref())? {
         let path = file?.path();
         if path.extension().and_then(|x| x.to_str()) == Some("json") {
-            let content = read_to_string(path)?;
+            let content = read_to_string(&path)?;
             let elements: Vec<Element> = serde_json::from_str(&content)?;
-            for element in elements {
-                match element {
-                    Element::Enum(e) => enums.push(e),
-                    Element::Table(t) => tables.push(t),
-                }
-            }
+            let entity = path
+                .file_stem()
+                .and_then(|x| x.to_str())
+                .unwrap_or_default();
+            entities.push((entity.to_owned(), elements));
         }
     }
-    Ok((enums, order(tables)))
+
+    Ok(ergol_core::state(entities))
+}
+
+/// Returns the db state from the `current` directory of the migrations, after checking that the
+/// types of its columns are defined.
+pub fn current_state<P: AsRef<Path>>(path: P) -> Result<State, Box<dyn Error>> {
+    let state = state_from_dir(path)?;
+    ergol_core::check_types(&state)?;
+    Ok(state)
 }
 
 /// Tries to find the database URL in Rocket.toml or Ergol.toml.
@@ -126,7 +111,29 @@ pub fn find_db_url<P: AsRef<Path>>(path: P) -> Option<String> {
 /// Runs the ergol migrations.
 pub async fn migrate<P: AsRef<Path>>(path: P) -> Result<(), Box<dyn Error>> {
     let path = path.as_ref();
-    let db_url = find_db_url(&path).unwrap();
+    let db_url = find_db_url(path).unwrap();
+
+    let (db, connection) = tokio_postgres::connect(&db_url, tokio_postgres::NoTls).await?;
+
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            eprintln!("connection error: {}", e);
+        }
+    });
+
+    run_migrations(path, &db).await
+}
+
+/// Runs the ergol migrations in every schema whose name matches the pattern.
+///
+/// The pattern is a SQL `LIKE` pattern, e.g. `tenant_%`. Each schema keeps track of its own
+/// migrations.
+pub async fn migrate_all_schemas<P: AsRef<Path>>(
+    path: P,
+    pattern: &str,
+) -> Result<(), Box<dyn Error>> {
+    let path = path.as_ref();
+    let db_url = find_db_url(path).unwrap();
 
     let (db, connection) = tokio_postgres::connect(&db_url, tokio_postgres::NoTls).await?;
 
@@ -136,12 +143,23 @@ pub async fn migrate<P: AsRef<Path>>(path: P) -> Result<(), Box<dyn Error>> {
         }
     });
 
-    let current = db::current_migration(&db).await?;
+    for schema in db::schemas(pattern, &db).await? {
+        println!("-- Schema {}", schema);
+        db::set_search_path(&schema, &db).await?;
+        run_migrations(path, &db).await?;
+    }
+
+    Ok(())
+}
+
+/// Runs the migrations that have not been run yet in the current schema.
+async fn run_migrations(path: &Path, db: &tokio_postgres::Client) -> Result<(), Box<dyn Error>> {
+    let current = db::current_migration(db).await?;
 
     let mut current = match current {
         Some(i) => i + 1,
         None => {
-            db::create_current_migration(&db).await?;
+            db::create_current_migration(db).await?;
             0
         }
     };
@@ -157,8 +175,8 @@ pub async fn migrate<P: AsRef<Path>>(path: P) -> Result<(), Box<dyn Error>> {
         let up = read_to_string(path)?;
         println!("{}", up);
 
-        db.simple_query(&up as &str).await?;
-        db::set_migration(current, &db).await?;
+        db::run(&up, db).await?;
+        db::set_migration(current, db).await?;
 
         current += 1;
     }
@@ -171,7 +189,7 @@ pub fn current_diff<P: AsRef<Path>>(path: P) -> Result<Diff, Box<dyn Error>> {
     let path = path.as_ref();
 
     let last = last_saved_state(path.join("migrations"))?;
-    let current = state_from_dir(path.join("migrations/current"))?;
+    let current = current_state(path.join("migrations/current"))?;
 
     Ok(diff(last.1, current))
 }
@@ -179,7 +197,7 @@ pub fn current_diff<P: AsRef<Path>>(path: P) -> Result<Diff, Box<dyn Error>> {
 /// Delete the whole database.
 pub async fn delete<P: AsRef<Path>>(path: P) -> Result<(), Box<dyn Error>> {
     let path = path.as_ref();
-    let db_url = find_db_url(&path).unwrap();
+    let db_url = find_db_url(path).unwrap();
 
     let (db, connection) = tokio_postgres::connect(&db_url, tokio_postgres::NoTls).await?;
 
@@ -189,7 +207,41 @@ pub async fn delete<P: AsRef<Path>>(path: P) -> Result<(), Box<dyn Error>> {
         }
     });
 
-    db::clear(&db).await?;
+    // The schemas of the current state are dropped with everything they contain.
+    let schemas = state_from_dir(path.join("migrations/current"))
+        .map(|(_, _, tables)| ergol_core::schemas(&tables))
+        .unwrap_or_default();
+
+    db::clear(&schemas, &db).await?;
+
+    Ok(())
+}
+
+/// Runs the program of the crate with `cargo run`, so that it writes its schema in the
+/// `migrations/current` directory.
+///
+/// The program must call `ergol::schema::dump_if_requested` at the beginning of its main
+/// function, and the arguments are given to `cargo run`, e.g. `--bin server`.
+pub fn dump_schema<P: AsRef<Path>>(p: P, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let p = p.as_ref();
+    let migrations = p.join("migrations");
+    create_dir_all(&migrations)?;
+
+    if !migrations.join(".gitignore").is_file() {
+        File::create(migrations.join(".gitignore"))?.write_all(b"current\n")?;
+    }
+
+    // The environment variable is read by ergol::schema::dump_if_requested.
+    let status = Command::new("cargo")
+        .arg("run")
+        .args(args)
+        .current_dir(p)
+        .env("ERGOL_DUMP_SCHEMA", migrations.join("current"))
+        .status()?;
+
+    if !status.success() {
+        return Err(format!("cargo run failed: {}", status).into());
+    }
 
     Ok(())
 }
@@ -198,7 +250,7 @@ pub async fn delete<P: AsRef<Path>>(path: P) -> Result<(), Box<dyn Error>> {
 pub fn save<P: AsRef<Path>>(p: P) -> Result<(), Box<dyn Error>> {
     let p = p.as_ref();
     let (last_index, last_state) = last_saved_state(p)?;
-    let current_state = state_from_dir(p.join("current"))?;
+    let current_state = current_state(p.join("current"))?;
     let current_index = match last_index {
         None => 0,
         Some(i) => i + 1,
@@ -208,7 +260,7 @@ pub fn save<P: AsRef<Path>>(p: P) -> Result<(), Box<dyn Error>> {
     create_dir(&save_dir)?;
     for f in read_dir(p.join("current"))? {
         let path = f?.path();
-        copy(&path, &save_dir.join(path.file_name().unwrap()))?;
+        copy(&path, save_dir.join(path.file_name().unwrap()))?;
     }
 
     let diff = diff(last_state, current_state);
@@ -225,7 +277,7 @@ pub fn save<P: AsRef<Path>>(p: P) -> Result<(), Box<dyn Error>> {
 pub async fn reset<P: AsRef<Path>>(p: P) -> Result<(), Box<dyn Error>> {
     let p = p.as_ref();
     delete(p).await?;
-    let (enums, tables) = state_from_dir(p.join("migrations/current"))?;
+    let state = current_state(p.join("migrations/current"))?;
 
     let db_url = find_db_url(p).unwrap();
     let (db, connection) = tokio_postgres::connect(&db_url, tokio_postgres::NoTls).await?;
@@ -236,12 +288,8 @@ pub async fn reset<P: AsRef<Path>>(p: P) -> Result<(), Box<dyn Error>> {
         }
     });
 
-    for e in enums {
-        db.query(&Element::Enum(e).create() as &str, &[]).await?;
-    }
-
-    for t in tables {
-        db.query(&Element::Table(t).create() as &str, &[]).await?;
+    for element in ergol_core::elements(state) {
+        db.batch_execute(&element.create()).await?;
     }
 
     Ok(())
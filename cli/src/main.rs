@@ -14,29 +14,32 @@ fn print_help() {
 {description}
 
 {USAGE}
-    {command} [SUBCOMMAND]
+    ergol [SUBCOMMAND]
 
 {FLAGS}
     {help_short}, {help_long}       Prints help information
     {version_short}, {version_long}    Prints version information
 
 {SUBCOMMANDS}
+    {dump_schema} Runs the program to write its schema in migrations/current
+                 (the arguments are given to cargo run, e.g. --bin server)
     {hint}       Gives a hint of the current migration
     {save}       Saves the current migration
     {delete}     Deletes everything in the database
     {migrate}    Runs all the migrations in the database
+                 (with --all-schemas <pattern>, runs them in every schema matching the pattern)
     {reset}      Deletes everything in the database and recreates an empty database"#,
         name = "ergol".green(),
         version = env!("CARGO_PKG_VERSION"),
         description = env!("CARGO_PKG_DESCRIPTION"),
         USAGE = "USAGE:".yellow(),
-        command = "ergol",
         FLAGS = "FLAGS:".yellow(),
         help_short = "-h".green(),
         help_long = "--help".green(),
         version_short = "-v".green(),
         version_long = "--version".green(),
         SUBCOMMANDS = "SUBCOMMANDS:".yellow(),
+        dump_schema = "dump-schema".green(),
         save = "save".green(),
         hint = "hint".green(),
         delete = "delete".green(),
@@ -75,20 +78,25 @@ async fn run() -> Result<(), Box<dyn Error>> {
     let cargo_toml = ergol_cli::find_cargo_toml().expect("couldn't find Cargo.toml");
 
     match args[1].as_ref() {
+        "dump-schema" => ergol_cli::dump_schema(cargo_toml, &args[2..])?,
         "hint" => println!("{}", ergol_cli::current_diff(cargo_toml)?.hint()),
         "save" => ergol_cli::save(cargo_toml.join("migrations"))?,
-        "migrate" => ergol_cli::migrate(cargo_toml).await?,
+        "migrate" => match args.iter().position(|x| x == "--all-schemas") {
+            Some(i) => {
+                let pattern = args.get(i + 1).ok_or("--all-schemas expects a pattern")?;
+                ergol_cli::migrate_all_schemas(cargo_toml, pattern).await?
+            }
+            None => ergol_cli::migrate(cargo_toml).await?,
+        },
         "delete" => ergol_cli::delete(cargo_toml).await?,
         "reset" => ergol_cli::reset(cargo_toml).await?,
 
         command => {
             // Unknwon command
             eprintln!(
-                "{}: {}{}{}",
+                "{}: command \"{}\" does not exist.",
                 "error".bold().red(),
-                "command \"",
-                command,
-                "\" does not exist."
+                command
             );
             print_help();
             exit(1);
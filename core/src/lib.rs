@@ -11,8 +11,18 @@ pub enum Element {
     /// An enum type.
     Enum(Enum),
 
+    /// A domain type.
+    Domain(Domain),
+
+    /// A type of the program stored as another type, that is only used to resolve the types of
+    /// the columns.
+    Alias(Alias),
+
     /// A table.
     Table(Table),
+
+    /// A schema.
+    Schema(Schema),
 }
 
 impl Element {
@@ -20,7 +30,10 @@ impl Element {
     pub fn create(&self) -> String {
         match self {
             Element::Enum(e) => e.create_type(),
+            Element::Domain(d) => d.create_domain(),
+            Element::Alias(_) => String::new(),
             Element::Table(t) => t.create_table(),
+            Element::Schema(s) => s.create_schema(),
         }
     }
 
@@ -28,9 +41,180 @@ impl Element {
     pub fn drop(&self) -> String {
         match self {
             Element::Enum(e) => e.drop_type(),
+            Element::Domain(d) => d.drop_domain(),
+            Element::Alias(_) => String::new(),
             Element::Table(t) => t.drop_table(),
+            Element::Schema(s) => s.drop_schema(),
+        }
+    }
+
+    /// Returns the query that drops the element if it exists, with everything that depends on it.
+    pub fn drop_if_exists(&self) -> String {
+        match self {
+            Element::Enum(e) => format!("DROP TYPE IF EXISTS \"{}\" CASCADE;", e.name),
+            Element::Domain(d) => format!("DROP DOMAIN IF EXISTS \"{}\" CASCADE;", d.name),
+            Element::Alias(_) => String::new(),
+            Element::Table(t) => format!("DROP TABLE IF EXISTS {} CASCADE;", t.qualified_name()),
+            Element::Schema(s) => format!("DROP SCHEMA IF EXISTS \"{}\" CASCADE;", s.name),
+        }
+    }
+}
+
+/// Returns the state of a database from the elements of the structs and the types, given with the
+/// names of the structs and the types.
+///
+/// The types of the columns that are aliases are resolved, the references between the tables are
+/// resolved, and the tables are sorted so that each table
+/// comes after the tables it references.
+pub fn state(entities: Vec<(String, Vec<Element>)>) -> (Vec<Enum>, Vec<Domain>, Vec<Table>) {
+    let mut tables = vec![];
+    let mut enums = vec![];
+    let mut domains = vec![];
+    let mut aliases = vec![];
+    let mut structs = vec![];
+
+    for (entity, elements) in entities {
+        // The first table of an entity is the table of the struct.
+        if let Some(Element::Table(t)) = elements.first() {
+            structs.push((
+                default_table_name(&entity),
+                t.schema.clone(),
+                t.name.clone(),
+            ));
+        }
+
+        for element in elements {
+            match element {
+                Element::Enum(e) => enums.push(e),
+                Element::Domain(d) => domains.push(d),
+                Element::Alias(a) => aliases.push(a),
+                Element::Table(t) => tables.push(t),
+                // The schemas are deduced from the tables.
+                Element::Schema(_) => (),
+            }
+        }
+    }
+
+    resolve_aliases(&mut tables, &aliases);
+    resolve_references(&mut tables, &structs);
+    (enums, domains, order(tables))
+}
+
+/// Replaces the types of the columns that are aliases by the types they stand for.
+pub fn resolve_aliases(tables: &mut [Table], aliases: &[Alias]) {
+    for table in tables.iter_mut() {
+        for column in &mut table.columns {
+            // An alias can stand for another alias, but not for itself.
+            for _ in 0..=aliases.len() {
+                let alias = match column.ty.named() {
+                    Some(name) => aliases.iter().find(|x| x.name == name),
+                    None => None,
+                };
+
+                match alias {
+                    Some(alias) => column.ty = column.ty.with_named(&alias.ty),
+                    None => break,
+                }
+            }
+        }
+    }
+}
+
+/// Checks that the enum and domain types used by the columns of the tables are defined in the
+/// state.
+pub fn check_types(
+    (enums, domains, tables): &(Vec<Enum>, Vec<Domain>, Vec<Table>),
+) -> Result<(), String> {
+    for table in tables {
+        for column in &table.columns {
+            if let Some(name) = column.ty.named() {
+                if !enums.iter().any(|x| x.name == name) && !domains.iter().any(|x| x.name == name)
+                {
+                    return Err(format!(
+                        "the column {} of the table {} has the type {}, which is neither an enum nor a domain",
+                        column.name, table.name, name
+                    ));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the elements of a state in the order in which they can be created: the schemas, the
+/// enums, the domains and the tables.
+pub fn elements((enums, domains, tables): (Vec<Enum>, Vec<Domain>, Vec<Table>)) -> Vec<Element> {
+    schemas(&tables)
+        .into_iter()
+        .map(|name| Element::Schema(Schema { name }))
+        .chain(enums.into_iter().map(Element::Enum))
+        .chain(domains.into_iter().map(Element::Domain))
+        .chain(tables.into_iter().map(Element::Table))
+        .collect()
+}
+
+/// Returns the schemas in which the tables live, without duplicates.
+pub fn schemas(tables: &[Table]) -> Vec<String> {
+    let mut schemas = tables
+        .iter()
+        .filter_map(|t| t.schema.clone())
+        .collect::<Vec<_>>();
+    schemas.sort();
+    schemas.dedup();
+    schemas
+}
+
+/// Tries to sort the tables in order to avoid problems with dependencies.
+pub fn order(tables: Vec<Table>) -> Vec<Table> {
+    let mut current: Vec<String> = vec![];
+    let mut output_tables = vec![];
+    let len = tables.len();
+
+    for _ in 0..len {
+        for table in &tables {
+            // Check dependencies
+            if !current.contains(&table.name)
+                && table.dependencies().iter().all(|x| current.contains(x))
+            {
+                current.push(table.name.clone());
+                output_tables.push(table.clone());
+            }
         }
     }
+
+    if output_tables.len() != len {
+        tables
+    } else {
+        output_tables
+    }
+}
+
+/// Returns the quoted name of a table, qualified with the schema in which it lives if any.
+pub fn qualified_name(schema: Option<&str>, name: &str) -> String {
+    match schema {
+        Some(schema) => format!("\"{}\".\"{}\"", schema, name),
+        None => format!("\"{}\"", name),
+    }
+}
+
+/// The struct that holds the information to create or drop a schema.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Schema {
+    /// The name of the schema.
+    pub name: String,
+}
+
+impl Schema {
+    /// Creates the schema.
+    pub fn create_schema(&self) -> String {
+        format!("CREATE SCHEMA \"{}\";\n", self.name)
+    }
+
+    /// Drops the schema.
+    pub fn drop_schema(&self) -> String {
+        format!("DROP SCHEMA \"{}\" CASCADE;", self.name)
+    }
 }
 
 /// The struct that holds to information to create, drop or migrate an enum type.
@@ -59,14 +243,84 @@ impl Enum {
     }
 }
 
+/// The struct that holds the information to create or drop a domain type.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Domain {
+    /// The name of the type.
+    pub name: String,
+
+    /// The postgres type on which the domain is based.
+    pub ty: String,
+
+    /// The condition that the values of the domain must satisfy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub check: Option<String>,
+}
+
+impl Domain {
+    /// Creates the domain.
+    pub fn create_domain(&self) -> String {
+        match &self.check {
+            Some(check) => format!(
+                "CREATE DOMAIN \"{}\" AS {} {};\n",
+                self.name,
+                self.ty,
+                self.check_constraint(check)
+            ),
+            None => format!("CREATE DOMAIN \"{}\" AS {};\n", self.name, self.ty),
+        }
+    }
+
+    /// Returns the name of the check constraint of the domain.
+    pub fn check_name(&self) -> String {
+        format!("{}_check", self.name)
+    }
+
+    /// Returns the check constraint of the domain for a condition.
+    pub fn check_constraint(&self, check: &str) -> String {
+        format!("CONSTRAINT \"{}\" CHECK ({})", self.check_name(), check)
+    }
+
+    /// Drops the domain.
+    pub fn drop_domain(&self) -> String {
+        format!("DROP DOMAIN \"{}\";", self.name)
+    }
+}
+
+/// The struct that holds the type that a type of the program stands for.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Alias {
+    /// The name of the type of the program, in snake case.
+    pub name: String,
+
+    /// The type that the alias stands for.
+    pub ty: Ty,
+}
+
 /// The struct that holds the information to create, drop or migrate a table.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Table {
     /// The name of the table.
     pub name: String,
 
+    /// The schema in which the table lives, if it is not the current schema.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<String>,
+
     /// The columns of the table.
     pub columns: Vec<Column>,
+
+    /// The names of the columns of the primary key, when it is made of several columns.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub primary_key: Vec<String>,
+
+    /// The secondary indexes of the table.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub indexes: Vec<Index>,
+
+    /// The constraints of the table, other than its primary key and its unique columns.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub constraints: Vec<Constraint>,
 }
 
 impl Table {
@@ -74,51 +328,312 @@ impl Table {
     pub fn new(name: &str) -> Table {
         Table {
             name: name.into(),
+            schema: None,
             columns: vec![],
+            primary_key: vec![],
+            indexes: vec![],
+            constraints: vec![],
         }
     }
 
+    /// Returns the quoted name of the table, qualified with its schema if any.
+    pub fn qualified_name(&self) -> String {
+        qualified_name(self.schema.as_deref(), &self.name)
+    }
+
     /// Returns the create table query for the table.
     pub fn create_table(&self) -> String {
-        format!(
-            "CREATE TABLE \"{}\" (\n    {}\n);\n",
-            self.name,
-            self.columns
-                .iter()
-                .map(|x| format!(
-                    "\"{}\" {}{}",
-                    x.name,
-                    x.ty.to_postgres(),
-                    if x.unique { " UNIQUE" } else { "" }
-                ))
-                .collect::<Vec<_>>()
-                .join(",\n    ")
-        )
+        let mut lines = self
+            .columns
+            .iter()
+            .map(Column::to_postgres)
+            .collect::<Vec<_>>();
+
+        if !self.primary_key.is_empty() {
+            lines.push(format!(
+                "PRIMARY KEY ({})",
+                self.primary_key
+                    .iter()
+                    .map(|x| format!("\"{}\"", x))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+
+        lines.extend(self.constraints.iter().map(Constraint::to_postgres));
+
+        let mut query = format!(
+            "CREATE TABLE {} (\n    {}\n);\n",
+            self.qualified_name(),
+            lines.join(",\n    ")
+        );
+
+        // The table is empty, so its indexes don't need to be created concurrently.
+        for index in &self.indexes {
+            query.push_str(&index.create_index(&self.qualified_name(), false));
+        }
+
+        query
     }
 
     /// Returns the drop table query for the table.
     pub fn drop_table(&self) -> String {
-        format!("DROP TABLE \"{}\" CASCADE;", self.name)
+        format!("DROP TABLE {} CASCADE;", self.qualified_name())
     }
 
     /// Creates the current migration table.
     pub fn current_migration() -> Table {
         Table {
             name: "ergol".into(),
+            schema: None,
             columns: vec![Column::new("migration", Ty::I32, false)],
+            primary_key: vec![],
+            indexes: vec![],
+            constraints: vec![],
         }
     }
 
     /// Returns the names of the tables on which self depends.
+    ///
+    /// A table that references itself does not depend on itself.
     pub fn dependencies(&self) -> Vec<String> {
         self.columns
             .iter()
-            .filter_map(|x| match &x.ty {
-                Ty::Reference(t) => Some(t.clone()),
-                _ => None,
-            })
+            .filter_map(|x| x.ty.reference().map(String::from))
+            .filter(|x| *x != self.name)
+            .collect()
+    }
+
+    /// Returns the names of the columns of the primary key of the table.
+    pub fn key_columns(&self) -> Vec<String> {
+        if !self.primary_key.is_empty() {
+            return self.primary_key.clone();
+        }
+
+        self.columns
+            .iter()
+            .filter(|x| x.ty.key().is_some())
+            .map(|x| x.name.clone())
             .collect()
     }
+
+    /// Returns the name and the type of the primary key of the table.
+    pub fn key(&self) -> Option<(&str, Ty)> {
+        self.columns
+            .iter()
+            .find_map(|x| x.ty.key().map(|ty| (&x.name as &str, ty)))
+    }
+}
+
+/// A secondary index on some columns of a table.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Index {
+    /// The name of the index.
+    pub name: String,
+
+    /// The names of the indexed columns.
+    pub columns: Vec<String>,
+
+    /// Whether the index is unique or not.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub unique: bool,
+
+    /// The index method, e.g. `gin`, if it is not the default one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub using: Option<String>,
+
+    /// The operator class of the columns, e.g. `jsonb_path_ops`, if it is not the default one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ops: Option<String>,
+
+    /// The predicate of a partial index.
+    #[serde(default, rename = "where", skip_serializing_if = "Option::is_none")]
+    pub predicate: Option<String>,
+}
+
+impl Index {
+    /// Returns the default name of an index on some columns of a table.
+    pub fn default_name(table: &str, columns: &[String]) -> String {
+        format!("{}_{}_idx", table, columns.join("_"))
+    }
+
+    /// Returns the create index query for the index, on the table with the given qualified name.
+    pub fn create_index(&self, table: &str, concurrently: bool) -> String {
+        format!(
+            "CREATE {}INDEX {}\"{}\" ON {}{} ({}){};\n",
+            if self.unique { "UNIQUE " } else { "" },
+            if concurrently { "CONCURRENTLY " } else { "" },
+            self.name,
+            table,
+            match &self.using {
+                Some(using) => format!(" USING {}", using),
+                None => String::new(),
+            },
+            self.columns
+                .iter()
+                .map(|x| match &self.ops {
+                    Some(ops) => format!("\"{}\" {}", x, ops),
+                    None => format!("\"{}\"", x),
+                })
+                .collect::<Vec<_>>()
+                .join(", "),
+            match &self.predicate {
+                Some(predicate) => format!(" WHERE {}", predicate),
+                None => String::new(),
+            },
+        )
+    }
+
+    /// Returns the drop index query for the index, that lives in the given schema.
+    pub fn drop_index(&self, schema: Option<&str>, concurrently: bool) -> String {
+        format!(
+            "DROP INDEX {}{};",
+            if concurrently { "CONCURRENTLY " } else { "" },
+            qualified_name(schema, &self.name)
+        )
+    }
+}
+
+/// A named constraint of a table.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Constraint {
+    /// The name of the constraint.
+    pub name: String,
+
+    /// The kind of the constraint.
+    #[serde(flatten)]
+    pub kind: ConstraintKind,
+}
+
+/// The kinds of constraints.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ConstraintKind {
+    /// The values of the columns with those names are unique together.
+    Unique(Vec<String>),
+
+    /// The rows must satisfy the expression.
+    Check(String),
+
+    /// No two rows have columns that all match with the operators, e.g. `=` for a room and `&&`
+    /// for a period.
+    Exclude {
+        /// The index method of the constraint, e.g. `gist`, if it is not the default one.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        using: Option<String>,
+
+        /// The names of the columns with their operators.
+        elements: Vec<(String, String)>,
+    },
+}
+
+impl Constraint {
+    /// Creates a unique constraint with the default name on some columns of a table.
+    pub fn unique(table: &str, columns: Vec<String>) -> Constraint {
+        Constraint {
+            name: format!("{}_{}_key", table, columns.join("_")),
+            kind: ConstraintKind::Unique(columns),
+        }
+    }
+
+    /// Creates a check constraint with the default name on a column of a table.
+    pub fn check(table: &str, column: &str, expression: &str) -> Constraint {
+        Constraint {
+            name: format!("{}_{}_check", table, column),
+            kind: ConstraintKind::Check(expression.into()),
+        }
+    }
+
+    /// Creates an exclusion constraint with the default name on some columns of a table, given
+    /// with their operators.
+    pub fn exclude(
+        table: &str,
+        using: Option<String>,
+        elements: Vec<(String, String)>,
+    ) -> Constraint {
+        let columns = elements.iter().map(|x| &x.0 as &str).collect::<Vec<_>>();
+        Constraint {
+            name: format!("{}_{}_excl", table, columns.join("_")),
+            kind: ConstraintKind::Exclude { using, elements },
+        }
+    }
+
+    /// Returns the postgres definition of the constraint.
+    pub fn to_postgres(&self) -> String {
+        match &self.kind {
+            ConstraintKind::Unique(columns) => format!(
+                "CONSTRAINT \"{}\" UNIQUE ({})",
+                self.name,
+                columns
+                    .iter()
+                    .map(|x| format!("\"{}\"", x))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            ConstraintKind::Check(expression) => {
+                format!("CONSTRAINT \"{}\" CHECK ({})", self.name, expression)
+            }
+            ConstraintKind::Exclude { using, elements } => format!(
+                "CONSTRAINT \"{}\" EXCLUDE {}({})",
+                self.name,
+                match using {
+                    Some(using) => format!("USING {} ", using),
+                    None => String::new(),
+                },
+                elements
+                    .iter()
+                    .map(|(column, operator)| format!("\"{}\" WITH {}", column, operator))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+/// Returns the name of the table of a struct that does not specify its table name.
+pub fn default_table_name(entity: &str) -> String {
+    format!("{}s", entity.to_snake())
+}
+
+/// Resolves the references between tables.
+///
+/// The `#[ergol]` macro only knows the name of the structs referenced by a struct, so it generates
+/// references to their default table name, that assume an `i32` primary key. This function
+/// replaces those references with foreign keys that have the name of the referenced table, and
+/// the type and name of its primary key.
+///
+/// The `structs` slice contains the default table names of the structs, associated with the
+/// schemas and the names of their tables, so that two tables with the same name in different
+/// schemas are told apart. A reference to a table that is not the table of a struct is resolved
+/// by the name of the table.
+pub fn resolve_references(tables: &mut [Table], structs: &[(String, Option<String>, String)]) {
+    let keys = tables
+        .iter()
+        .filter_map(|t| {
+            t.key()
+                .map(|(n, ty)| (t.name.clone(), t.schema.clone(), n.to_owned(), ty))
+        })
+        .collect::<Vec<_>>();
+
+    for table in tables.iter_mut() {
+        for column in &mut table.columns {
+            if let Some(name) = column.ty.reference().map(String::from) {
+                let key = match structs.iter().find(|x| x.0 == name) {
+                    Some((_, schema, real)) => keys.iter().find(|x| &x.0 == real && &x.1 == schema),
+                    None => keys.iter().find(|x| x.0 == name),
+                };
+
+                if let Some((table, schema, key, ty)) = key {
+                    column.ty = column.ty.with_reference(ForeignKey {
+                        table: table.clone(),
+                        schema: schema.clone(),
+                        column: key.clone(),
+                        ty: Box::new(ty.clone()),
+                    });
+                }
+            }
+        }
+    }
 }
 
 /// A column of a table.
@@ -132,6 +647,23 @@ pub struct Column {
 
     /// Whether the column is unique or not.
     pub unique: bool,
+
+    /// The default value of the column, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+
+    /// What happens to the row when the referenced row is deleted, if the column is a reference.
+    /// The row is deleted if it is not specified.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_delete: Option<ReferentialAction>,
+
+    /// What happens to the row when the referenced key is updated, if the column is a reference.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_update: Option<ReferentialAction>,
+
+    /// The expression that computes the values of the column, if the column is generated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generated: Option<String>,
 }
 
 impl Column {
@@ -141,8 +673,152 @@ impl Column {
             name: name.into(),
             ty,
             unique,
+            default: None,
+            on_delete: None,
+            on_update: None,
+            generated: None,
+        }
+    }
+
+    /// Creates the generated column that holds the full text search vector of a text column, in
+    /// a text search configuration, e.g. `english`.
+    pub fn fulltext(column: &str, lang: &str) -> Column {
+        Column {
+            generated: Some(format!(
+                "to_tsvector('{}', coalesce(\"{}\", ''))",
+                lang.replace('\'', "''"),
+                column
+            )),
+            ..Column::new(&Column::fulltext_name(column), Ty::TsVector, false)
         }
     }
+
+    /// Returns the name of the generated column that holds the full text search vector of a text
+    /// column.
+    pub fn fulltext_name(column: &str) -> String {
+        format!("{}_tsv", column)
+    }
+
+    /// Sets the default value of the column.
+    pub fn with_default(mut self, default: &str) -> Column {
+        self.default = Some(default.into());
+        self
+    }
+
+    /// Returns the referential actions of the column, if it is a reference.
+    pub fn actions(&self) -> String {
+        if self.ty.reference().is_some() {
+            referential_actions(self.on_delete, self.on_update)
+        } else {
+            String::new()
+        }
+    }
+
+    /// Returns the postgres definition of the column.
+    pub fn to_postgres(&self) -> String {
+        format!(
+            "\"{}\" {}{}{}{}",
+            self.name,
+            self.ty.to_postgres(),
+            self.actions(),
+            if self.unique { " UNIQUE" } else { "" },
+            match (&self.default, &self.generated) {
+                (Some(default), _) => format!(" DEFAULT {}", default),
+                (None, Some(generated)) => format!(" GENERATED ALWAYS AS ({}) STORED", generated),
+                (None, None) => String::new(),
+            }
+        )
+    }
+}
+
+/// What happens to a row when the row it references is deleted or updated.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReferentialAction {
+    /// The row is deleted or updated as well.
+    Cascade,
+
+    /// The referenced row can't be deleted or updated.
+    Restrict,
+
+    /// The reference is set to null.
+    SetNull,
+}
+
+impl ReferentialAction {
+    /// Returns the postgres representation of the action.
+    pub fn to_postgres(&self) -> &'static str {
+        match self {
+            ReferentialAction::Cascade => "CASCADE",
+            ReferentialAction::Restrict => "RESTRICT",
+            ReferentialAction::SetNull => "SET NULL",
+        }
+    }
+}
+
+impl FromStr for ReferentialAction {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cascade" => Ok(ReferentialAction::Cascade),
+            "restrict" => Ok(ReferentialAction::Restrict),
+            "set_null" => Ok(ReferentialAction::SetNull),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Returns the postgres clauses of the referential actions of a reference, which deletes the row
+/// when the referenced row is deleted unless specified otherwise.
+pub fn referential_actions(
+    on_delete: Option<ReferentialAction>,
+    on_update: Option<ReferentialAction>,
+) -> String {
+    let mut output = format!(
+        " ON DELETE {}",
+        on_delete
+            .unwrap_or(ReferentialAction::Cascade)
+            .to_postgres()
+    );
+
+    if let Some(on_update) = on_update {
+        output.push_str(&format!(" ON UPDATE {}", on_update.to_postgres()));
+    }
+
+    output
+}
+
+/// A reference to the primary key of another table.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ForeignKey {
+    /// The name of the referenced table.
+    pub table: String,
+
+    /// The schema of the referenced table, if it is not the current schema.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<String>,
+
+    /// The name of the referenced column.
+    pub column: String,
+
+    /// The type of the referenced column.
+    pub ty: Box<Ty>,
+}
+
+impl ForeignKey {
+    /// Returns the postgres representation of the foreign key.
+    pub fn to_postgres(&self) -> String {
+        format!("{} {}", self.ty.to_postgres(), self.references())
+    }
+
+    /// Returns the references clause of the foreign key.
+    pub fn references(&self) -> String {
+        format!(
+            "REFERENCES {} (\"{}\")",
+            qualified_name(self.schema.as_deref(), &self.table),
+            self.column,
+        )
+    }
 }
 
 /// The type of a column.
@@ -151,6 +827,18 @@ pub enum Ty {
     /// An ID column.
     Id,
 
+    /// A 64 bits ID column.
+    BigId,
+
+    /// A primary key whose value is given by the user or by a default value.
+    PrimaryKey(Box<Ty>),
+
+    /// An i8 column, stored as a single byte character.
+    I8,
+
+    /// An i16 column.
+    I16,
+
     /// An i32 column.
     I32,
 
@@ -163,6 +851,9 @@ pub enum Ty {
     /// An f64 column.
     F64,
 
+    /// A u32 column, stored as an object identifier.
+    U32,
+
     /// A boolean column.
     Bool,
 
@@ -172,6 +863,24 @@ pub enum Ty {
     /// A JSON value.
     Json,
 
+    /// A JSON value stored in the binary format of postgres.
+    Jsonb,
+
+    /// A byte array.
+    Bytes,
+
+    /// A rust_decimal decimal.
+    Decimal,
+
+    /// An IP address.
+    IpAddr,
+
+    /// A time interval.
+    Interval,
+
+    /// A full text search vector.
+    TsVector,
+
     /// A bit vec.
     BitVec,
 
@@ -223,11 +932,20 @@ pub enum Ty {
     /// An optional type.
     Option(Box<Ty>),
 
-    /// An enum type.
+    /// An array type.
+    Array(Box<Ty>),
+
+    /// A range type, whose bounds have the inner type.
+    Range(Box<Ty>),
+
+    /// An enum or a domain type.
     Enum(String),
 
     /// A reference to another type.
     Reference(String),
+
+    /// A reference to the primary key of another table.
+    ForeignKey(ForeignKey),
 }
 
 impl Ty {
@@ -235,13 +953,24 @@ impl Ty {
     pub fn to_postgres(&self) -> String {
         match self {
             Ty::Id => "SERIAL PRIMARY KEY".to_owned(),
+            Ty::BigId => "BIGSERIAL PRIMARY KEY".to_owned(),
+            Ty::PrimaryKey(ty) => format!("{} PRIMARY KEY", ty.to_postgres()),
             Ty::String => "VARCHAR NOT NULL".to_owned(),
+            Ty::I8 => "\"char\" NOT NULL".to_owned(),
+            Ty::I16 => "SMALLINT NOT NULL".to_owned(),
             Ty::I32 => "INT NOT NULL".to_owned(),
             Ty::I64 => "BIGINT NOT NULL".to_owned(),
             Ty::F32 => "REAL NOT NULL".to_owned(),
             Ty::F64 => "DOUBLE PRECISION NOT NULL".to_owned(),
+            Ty::U32 => "OID NOT NULL".to_owned(),
             Ty::Bool => "BOOL NOT NULL".to_owned(),
             Ty::Json => "JSON NOT NULL".to_owned(),
+            Ty::Jsonb => "JSONB NOT NULL".to_owned(),
+            Ty::Bytes => "BYTEA NOT NULL".to_owned(),
+            Ty::Decimal => "NUMERIC NOT NULL".to_owned(),
+            Ty::IpAddr => "INET NOT NULL".to_owned(),
+            Ty::Interval => "INTERVAL NOT NULL".to_owned(),
+            Ty::TsVector => "TSVECTOR NOT NULL".to_owned(),
             Ty::BitVec => "VARBIT NOT NULL".to_owned(),
             Ty::NaiveDateTime => "TIMESTAMP NOT NULL".to_owned(),
             Ty::DateTimeUtc | Ty::DateTimeLocal | Ty::DateTimeFixedOffset => {
@@ -259,65 +988,207 @@ impl Ty {
             Ty::Date => "DATE NOT NULL".to_owned(),
             Ty::Time => "TIME NOT NULL".to_owned(),
             Ty::Option(ty) => {
+                // The NOT NULL is not always at the end, e.g. for foreign keys.
                 let current = ty.to_postgres();
-                debug_assert!(current.ends_with(" NOT NULL"));
-                current[0..current.len() - 9].to_owned()
+                debug_assert!(current.contains(" NOT NULL"));
+                current.replacen(" NOT NULL", "", 1)
             }
+            Ty::Array(ty) => {
+                // The elements of an array can always be null in postgres.
+                format!(
+                    "{}[] NOT NULL",
+                    ty.to_postgres().replacen(" NOT NULL", "", 1)
+                )
+            }
+            Ty::Range(ty) => format!("{} NOT NULL", ty.range().unwrap_or("ANYRANGE")),
             Ty::Enum(s) => format!("{} NOT NULL", s.to_snake()),
-            Ty::Reference(s) => format!(
-                "INT NOT NULL REFERENCES {} (id) ON DELETE CASCADE",
-                s.to_snake()
-            ),
+            Ty::Reference(s) => format!("INT NOT NULL REFERENCES {} (id)", s.to_snake()),
+            Ty::ForeignKey(f) => f.to_postgres(),
+        }
+    }
+
+    /// Returns the type of the values of the key if self is a primary key.
+    pub fn key(&self) -> Option<Ty> {
+        match self {
+            Ty::Id => Some(Ty::I32),
+            Ty::BigId => Some(Ty::I64),
+            Ty::PrimaryKey(ty) => Some(*ty.clone()),
+            _ => None,
+        }
+    }
+
+    /// Returns the postgres range type whose bounds have the type self, if any.
+    pub fn range(&self) -> Option<&'static str> {
+        match self {
+            Ty::I32 => Some("INT4RANGE"),
+            Ty::I64 => Some("INT8RANGE"),
+            Ty::Decimal => Some("NUMRANGE"),
+            Ty::NaiveDateTime | Ty::PrimitiveDateTime => Some("TSRANGE"),
+            Ty::DateTimeUtc | Ty::DateTimeLocal | Ty::DateTimeFixedOffset | Ty::OffsetDateTime => {
+                Some("TSTZRANGE")
+            }
+            Ty::NaiveDate | Ty::Date => Some("DATERANGE"),
+            _ => None,
+        }
+    }
+
+    /// Returns the type of the values of self if self is optional, self otherwise.
+    pub fn inner(&self) -> &Ty {
+        match self {
+            Ty::Option(ty) => ty,
+            ty => ty,
+        }
+    }
+
+    /// Returns the name of the enum or domain type of the values of self, if any.
+    pub fn named(&self) -> Option<&str> {
+        match self {
+            Ty::Enum(s) => Some(s),
+            Ty::PrimaryKey(ty) | Ty::Option(ty) | Ty::Array(ty) => ty.named(),
+            _ => None,
+        }
+    }
+
+    /// Replaces the enum or domain type of the values of self.
+    fn with_named(&self, ty: &Ty) -> Ty {
+        match self {
+            Ty::Enum(_) => ty.clone(),
+            Ty::PrimaryKey(inner) => Ty::PrimaryKey(Box::new(inner.with_named(ty))),
+            Ty::Option(inner) => Ty::Option(Box::new(inner.with_named(ty))),
+            Ty::Array(inner) => Ty::Array(Box::new(inner.with_named(ty))),
+            _ => self.clone(),
+        }
+    }
+
+    /// Returns the name of the referenced table if self is a reference.
+    pub fn reference(&self) -> Option<&str> {
+        match self {
+            Ty::Reference(t) => Some(t),
+            Ty::ForeignKey(f) => Some(&f.table),
+            Ty::Option(ty) => ty.reference(),
+            _ => None,
+        }
+    }
+
+    /// Returns the foreign key if self is a resolved reference.
+    pub fn foreign_key(&self) -> Option<&ForeignKey> {
+        match self {
+            Ty::ForeignKey(f) => Some(f),
+            Ty::Option(ty) => ty.foreign_key(),
+            _ => None,
+        }
+    }
+
+    /// Replaces the reference in self by a foreign key.
+    fn with_reference(&self, foreign_key: ForeignKey) -> Ty {
+        match self {
+            Ty::Option(ty) => Ty::Option(Box::new(ty.with_reference(foreign_key))),
+            _ => Ty::ForeignKey(foreign_key),
         }
     }
 }
 
+/// Returns the content of the outermost chevrons, so that the nested types are kept whole.
 fn extract_chevrons(pattern: &str) -> Option<&str> {
-    Some(pattern.split("<").nth(1)?.split(">").nth(0)?.trim())
+    Some(pattern.split_once('<')?.1.rsplit_once('>')?.0.trim())
 }
 
-impl FromStr for Ty {
-    type Err = ();
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
+/// Returns true if the type is a path without generic arguments, e.g. `Role` or `types :: Role`.
+fn is_path(ty: &str) -> bool {
+    !ty.is_empty()
+        && ty
+            .split("::")
+            .map(str::trim)
+            .all(|x| !x.is_empty() && x.chars().all(|c| c.is_alphanumeric() || c == '_'))
+}
+
+impl Ty {
+    /// Parses a type of rust, where `types` gives the name of the postgres type of the types
+    /// defined by the program, e.g. the enums deriving `PgEnum`, and returns `None` for the types
+    /// that can not be stored.
+    ///
+    /// The types of the program take precedence over the types that are recognized by their
+    /// name only, so that an enum named `Value` is not mistaken for `serde_json::Value`.
+    pub fn parse(s: &str, types: &dyn Fn(&str) -> Option<String>) -> Option<Ty> {
         match s {
-            "String" => return Ok(Ty::String),
-            "i32" => return Ok(Ty::I32),
-            "i64" => return Ok(Ty::I64),
-            "f32" => return Ok(Ty::F32),
-            "f64" => return Ok(Ty::F64),
-            "bool" => return Ok(Ty::Bool),
-            "BitVec" => return Ok(Ty::BitVec),
-            "NaiveDateTime" => return Ok(Ty::NaiveDateTime),
-            "NaiveDate" => return Ok(Ty::NaiveDate),
-            "NaiveTime" => return Ok(Ty::NaiveTime),
-            "MacAddress" => return Ok(Ty::MacAddress),
-            "Uuid" => return Ok(Ty::Uuid),
-            "PrimitiveDateTime" => return Ok(Ty::PrimitiveDateTime),
-            "OffsetDateTime" => return Ok(Ty::OffsetDateTime),
-            "Date" => return Ok(Ty::Date),
-            "Time" => return Ok(Ty::Time),
+            "String" => return Some(Ty::String),
+            "i8" => return Some(Ty::I8),
+            "i16" => return Some(Ty::I16),
+            "i32" => return Some(Ty::I32),
+            "i64" => return Some(Ty::I64),
+            "f32" => return Some(Ty::F32),
+            "f64" => return Some(Ty::F64),
+            "u32" => return Some(Ty::U32),
+            "bool" => return Some(Ty::Bool),
+            "Vec < u8 >" => return Some(Ty::Bytes),
+            "rust_decimal :: Decimal" => return Some(Ty::Decimal),
+            "std :: net :: IpAddr" => return Some(Ty::IpAddr),
+            "serde_json :: Value" => return Some(Ty::Jsonb),
+            "ergol :: pg :: Interval" => return Some(Ty::Interval),
             _ => (),
         }
 
+        if is_path(s) {
+            let name = s.rsplit("::").next()?.trim();
+            if let Some(name) = types(name) {
+                return Some(Ty::Enum(name));
+            }
+        }
+
+        match s {
+            "Decimal" => return Some(Ty::Decimal),
+            "IpAddr" => return Some(Ty::IpAddr),
+            "Value" => return Some(Ty::Jsonb),
+            "Interval" => return Some(Ty::Interval),
+            "BitVec" => return Some(Ty::BitVec),
+            "NaiveDateTime" => return Some(Ty::NaiveDateTime),
+            "NaiveDate" => return Some(Ty::NaiveDate),
+            "NaiveTime" => return Some(Ty::NaiveTime),
+            "MacAddress" => return Some(Ty::MacAddress),
+            "Uuid" => return Some(Ty::Uuid),
+            "PrimitiveDateTime" => return Some(Ty::PrimitiveDateTime),
+            "OffsetDateTime" => return Some(Ty::OffsetDateTime),
+            "Date" => return Some(Ty::Date),
+            "Time" => return Some(Ty::Time),
+            _ => (),
+        }
+
+        let inner = || Ty::parse(extract_chevrons(s)?, types);
+
         if s.starts_with("Option <") {
-            Self::from_str(extract_chevrons(s).ok_or(())?).map(|x| Ty::Option(Box::new(x)))
-        } else if s.starts_with("Json <") {
-            Ok(Ty::Json)
+            inner().map(|x| Ty::Option(Box::new(x)))
+        } else if s.starts_with("Vec <") {
+            inner().map(|x| Ty::Array(Box::new(x)))
+        } else if s.starts_with("Range <") || s.starts_with("ergol :: pg :: Range <") {
+            // Only the types that have a range type in postgres can be the bounds of a range.
+            match inner()? {
+                ty if ty.range().is_some() => Some(Ty::Range(Box::new(ty))),
+                _ => None,
+            }
+        } else if s.starts_with("Json <") || s.starts_with("Jsonb <") {
+            Some(Ty::Jsonb)
         } else if s.starts_with("Point <") && extract_chevrons(s) == Some("f64") {
-            Ok(Ty::Point)
+            Some(Ty::Point)
         } else if s.starts_with("Rect <") && extract_chevrons(s) == Some("f64") {
-            Ok(Ty::Rect)
+            Some(Ty::Rect)
         } else if s.starts_with("LineString <") && extract_chevrons(s) == Some("f64") {
-            Ok(Ty::LineString)
+            Some(Ty::LineString)
         } else if s.starts_with("DateTime <") {
-            match extract_chevrons(s).ok_or(())? {
-                "Utc" | "chrono :: Utc" => Ok(Ty::DateTimeUtc),
-                "Local" | "chrono :: Local" => Ok(Ty::DateTimeLocal),
-                "FixedOffset" | "chrono :: FixedOffset" => Ok(Ty::DateTimeFixedOffset),
-                _ => Err(()),
+            match extract_chevrons(s)? {
+                "Utc" | "chrono :: Utc" => Some(Ty::DateTimeUtc),
+                "Local" | "chrono :: Local" => Some(Ty::DateTimeLocal),
+                "FixedOffset" | "chrono :: FixedOffset" => Some(Ty::DateTimeFixedOffset),
+                _ => None,
             }
         } else {
-            Ok(Ty::Enum(s.to_snake()))
+            None
         }
     }
 }
+
+impl FromStr for Ty {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ty::parse(s, &|_| None).ok_or(())
+    }
+}
@@ -0,0 +1,152 @@
+//! This module contains the errors returned by the queries.
+
+use std::fmt;
+
+use tokio_postgres::error::SqlState;
+
+/// The errors returned by the queries.
+///
+/// The errors of the database are converted with `?`, and the violations of the constraints are
+/// reported with the name of their constraint.
+///
+/// ```
+/// # use ergol::prelude::*;
+/// # use ergol::tokio;
+/// #[ergol]
+/// #[derive(Clone)]
+/// pub struct Subscriber {
+///     #[id] pub id: i32,
+///     #[unique] pub email: String,
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), ergol::Error> {
+/// #     let (client, connection) = ergol::connect(
+/// #         "host=localhost user=ergol password=ergol dbname=ergol",
+/// #         ergol::tokio_postgres::NoTls,
+/// #     )
+/// #     .await?;
+/// #     tokio::spawn(async move {
+/// #         if let Err(e) = connection.await {
+/// #             eprintln!("connection error: {}", e);
+/// #         }
+/// #     });
+/// # ergol::drop_all(&client).await?;
+/// # ergol::create_all(&client).await?;
+/// let subscriber = Subscriber::create("hugo@example.com").save(&client).await?;
+///
+/// // The email is already taken.
+/// let error = Subscriber::create("hugo@example.com").save(&client).await.unwrap_err();
+/// assert!(matches!(error, ergol::Error::UniqueViolation { .. }));
+///
+/// // The error of the database is kept as the source of the violation.
+/// assert!(std::error::Error::source(&error).is_some());
+///
+/// // The subscriber can not be saved once it was deleted.
+/// subscriber.clone().delete(&client).await?;
+/// let error = subscriber.save(&client).await.unwrap_err();
+/// assert!(matches!(error, ergol::Error::Stale));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub enum Error {
+    /// The row that was looked for does not exist, e.g. the row referenced by a relation.
+    NotFound,
+
+    /// A column of a row can not be read as the type of its field.
+    Decode {
+        /// The name of the column.
+        column: String,
+
+        /// The error of the conversion.
+        source: tokio_postgres::Error,
+    },
+
+    /// The query would break a unique constraint.
+    UniqueViolation {
+        /// The name of the constraint.
+        constraint: String,
+
+        /// The error of the database.
+        source: tokio_postgres::Error,
+    },
+
+    /// The query would break a foreign key constraint.
+    ForeignKeyViolation {
+        /// The name of the constraint.
+        constraint: String,
+
+        /// The error of the database.
+        source: tokio_postgres::Error,
+    },
+
+    /// The row that was saved no longer exists in the database.
+    Stale,
+
+    /// Any other error of the database.
+    Db(tokio_postgres::Error),
+}
+
+impl Error {
+    /// Creates the error of a column that can not be read.
+    pub fn decode(column: &str, source: tokio_postgres::Error) -> Error {
+        Error::Decode {
+            column: column.to_owned(),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NotFound => write!(f, "row not found"),
+            Error::Decode { column, source } => {
+                write!(f, "error decoding column \"{}\": {}", column, source)
+            }
+            Error::UniqueViolation { constraint, .. } => {
+                write!(f, "unique constraint \"{}\" violated", constraint)
+            }
+            Error::ForeignKeyViolation { constraint, .. } => {
+                write!(f, "foreign key constraint \"{}\" violated", constraint)
+            }
+            Error::Stale => write!(f, "the row no longer exists"),
+            Error::Db(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Decode { source, .. }
+            | Error::UniqueViolation { source, .. }
+            | Error::ForeignKeyViolation { source, .. } => Some(source),
+            Error::Db(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<tokio_postgres::Error> for Error {
+    fn from(e: tokio_postgres::Error) -> Error {
+        let constraint = e
+            .as_db_error()
+            .and_then(|x| x.constraint())
+            .unwrap_or_default()
+            .to_owned();
+
+        match e.code() {
+            Some(code) if *code == SqlState::UNIQUE_VIOLATION => Error::UniqueViolation {
+                constraint,
+                source: e,
+            },
+            Some(code) if *code == SqlState::FOREIGN_KEY_VIOLATION => Error::ForeignKeyViolation {
+                constraint,
+                source: e,
+            },
+            _ => Error::Db(e),
+        }
+    }
+}
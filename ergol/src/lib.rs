@@ -29,7 +29,7 @@
 //! # }
 //! # use ergol::tokio;
 //! # #[tokio::main]
-//! # async fn main() -> Result<(), ergol::tokio_postgres::Error> {
+//! # async fn main() -> Result<(), ergol::Error> {
 //! #     let (client, connection) = ergol::connect(
 //! #         "host=localhost user=ergol password=ergol dbname=ergol",
 //! #         ergol::tokio_postgres::NoTls,
@@ -66,9 +66,11 @@
 //!
 //! See [the book](ergol-rs.github.io) for more information.
 
+pub mod error;
 pub mod pg;
 pub mod query;
 pub mod relation;
+pub mod schema;
 
 use crate::query::{CreateTable, DropTable, Select};
 
@@ -78,22 +80,55 @@ use crate::query::{CreateTable, DropTable, Select};
 /// trait for your structs.
 #[async_trait::async_trait]
 pub trait ToTable: Send + std::fmt::Debug + Sized {
-    /// Converts a row of a table into an object.
-    fn from_row_with_offset(row: &tokio_postgres::Row, offset: usize) -> Self;
+    /// The type of the primary key of the table, which is a tuple when the key is composite.
+    type Id: Clone + std::fmt::Debug + Send + Sync;
+
+    /// Converts a row of a table into an object, reading its columns from an offset.
+    ///
+    /// Returns a decode error when a column can not be read as the type of its field.
+    fn from_row_with_offset(row: &tokio_postgres::Row, offset: usize) -> Result<Self, Error>;
 
     /// Converts a row of a table into an object.
-    fn from_row(row: &tokio_postgres::Row) -> Self {
+    fn from_row(row: &tokio_postgres::Row) -> Result<Self, Error> {
         Self::from_row_with_offset(row, 0)
     }
 
+    /// Returns the number of columns of the table, which are read by `from_row_with_offset`.
+    fn column_count() -> usize;
+
     /// Returns the name of the table corresponding to Self.
     fn table_name() -> &'static str;
 
+    /// Returns the name of the schema in which the table lives, if it is not the current schema.
+    fn schema_name() -> Option<&'static str> {
+        None
+    }
+
+    /// Returns the quoted name of the table, qualified with its schema if any, ready to be used in
+    /// a query.
+    fn qualified_table_name() -> String {
+        match Self::schema_name() {
+            Some(schema) => format!("\"{}\".\"{}\"", schema, Self::table_name()),
+            None => format!("\"{}\"", Self::table_name()),
+        }
+    }
+
     /// Returns the name of the primary key of the table corresponding to Self.
     fn id_name() -> &'static str;
 
     /// Returns the id of self.
-    fn id(&self) -> i32;
+    fn id(&self) -> Self::Id;
+
+    /// Returns the name of the column marked as `#[soft_delete]`, if any.
+    fn soft_delete_name() -> Option<&'static str> {
+        None
+    }
+
+    /// Returns the key of the json field that holds the name of the variant of the values of a
+    /// column, if the type of the column is stored as internally tagged json.
+    fn column_tag(_column: &str) -> Option<&'static str> {
+        None
+    }
 
     /// Returns the query that creates the table.
     fn create_table() -> CreateTable;
@@ -107,9 +142,18 @@ pub trait ToTable: Send + std::fmt::Debug + Sized {
 
 pub use async_trait;
 pub use bytes;
+pub use ergol_core;
+#[doc(hidden)]
+pub use inventory;
+#[doc(hidden)]
+pub use serde_json;
 pub use tokio;
 pub use tokio_postgres;
 
+pub use error::Error;
+pub use query::raw;
+pub use schema::{create_all, drop_all};
+
 pub use ergol_proc_macro::ergol;
 
 /// Any enum that has no field on any variant can derive `PgEnum` in order to be usable in a
@@ -133,16 +177,253 @@ pub use ergol_proc_macro::ergol;
 ///     IAmNotOk,
 /// }
 /// ```
+///
+/// The variants are stored in snake case by default. The `#[pg_enum(rename_all = "...")]`
+/// attribute of the enum changes the case of the labels in the database, and the
+/// `#[pg_enum(rename = "...")]` attribute of a variant changes its label. The values are only
+/// accepted for the postgres enum type of the enum, and decoding a label that the enum doesn't
+/// know is an error.
+///
+/// ```
+/// # use ergol::prelude::*;
+/// # use ergol::tokio;
+/// #[derive(PgEnum, Debug, PartialEq)]
+/// #[pg_enum(rename_all = "SCREAMING_SNAKE_CASE")]
+/// pub enum Level {
+///     Low,
+///     #[pg_enum(rename = "MEDIUM_LEVEL")]
+///     Medium,
+///     VeryHigh,
+/// }
+///
+/// #[ergol]
+/// pub struct Measure {
+///     #[id] pub id: i32,
+///     pub level: Level,
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), ergol::Error> {
+/// #     let (client, connection) = ergol::connect(
+/// #         "host=localhost user=ergol password=ergol dbname=ergol",
+/// #         ergol::tokio_postgres::NoTls,
+/// #     )
+/// #     .await?;
+/// #     tokio::spawn(async move {
+/// #         if let Err(e) = connection.await {
+/// #             eprintln!("connection error: {}", e);
+/// #         }
+/// #     });
+/// # ergol::drop_all(&client).await?;
+/// # ergol::create_all(&client).await?;
+/// Measure::create(Level::Medium).save(&client).await?;
+/// Measure::create(Level::VeryHigh).save(&client).await?;
+///
+/// // The labels can be checked with the underlying client.
+/// let db = client.client();
+/// let rows = db.query("SELECT level::text FROM measures ORDER BY id", &[]).await?;
+/// assert_eq!(rows[0].get::<_, String>(0), "MEDIUM_LEVEL");
+/// assert_eq!(rows[1].get::<_, String>(0), "VERY_HIGH");
+///
+/// // A label added to the type in the database can not be decoded.
+/// db.execute("ALTER TYPE level ADD VALUE 'EXTREME'", &[]).await?;
+/// db.execute("UPDATE measures SET level = 'EXTREME'", &[]).await?;
+/// let row = db.query_one("SELECT level FROM measures LIMIT 1", &[]).await?;
+/// assert!(row.try_get::<_, Level>(0).is_err());
+///
+/// // A text is not a level.
+/// let row = db.query_one("SELECT 'LOW'::text", &[]).await?;
+/// assert!(row.try_get::<_, Level>(0).is_err());
+/// # Ok(())
+/// # }
+/// ```
 pub use ergol_proc_macro::PgEnum;
 
+/// Any type that implements `Serialize` and `Deserialize` can derive `PgJson` in order to be
+/// stored as json in a `#[ergol]` struct, e.g. an enum whose variants have fields.
+///
+/// The values are stored in a postgres domain over `JSONB` that has the name of the type in snake
+/// case. The columns of the type have a `has_tag` filter that keeps the values of a variant: it
+/// compares the field given by the `#[serde(tag = "...")]` attribute of the type, or the key of
+/// the value for the externally tagged enums.
+///
+/// ```
+/// # use ergol::prelude::*;
+/// # use ergol::tokio;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(PgJson, Serialize, Deserialize, Debug, PartialEq)]
+/// #[serde(tag = "type")]
+/// pub enum Payment {
+///     Card { last4: String },
+///     Transfer { iban: String },
+/// }
+///
+/// #[ergol]
+/// pub struct Order {
+///     #[id] pub id: i32,
+///     pub payment: Payment,
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), ergol::Error> {
+/// #     let (client, connection) = ergol::connect(
+/// #         "host=localhost user=ergol password=ergol dbname=ergol",
+/// #         ergol::tokio_postgres::NoTls,
+/// #     )
+/// #     .await?;
+/// #     tokio::spawn(async move {
+/// #         if let Err(e) = connection.await {
+/// #             eprintln!("connection error: {}", e);
+/// #         }
+/// #     });
+/// # ergol::drop_all(&client).await?;
+/// # ergol::create_all(&client).await?;
+/// let card = Payment::Card { last4: String::from("4242") };
+/// Order::create(card).save(&client).await?;
+///
+/// let transfer = Payment::Transfer { iban: String::from("FR76") };
+/// Order::create(transfer).save(&client).await?;
+///
+/// let cards = Order::select()
+///     .filter(order::payment::has_tag("Card"))
+///     .execute(&client)
+///     .await?;
+///
+/// assert_eq!(cards.len(), 1);
+/// assert_eq!(cards[0].payment, Payment::Card { last4: String::from("4242") });
+/// # Ok(())
+/// # }
+/// ```
+pub use ergol_proc_macro::PgJson;
+
+/// A struct with a single unnamed field can derive `PgType` in order to be usable in a `#[ergol]`
+/// struct, with the postgres type of its field.
+///
+/// With the `#[pg_type(domain)]` attribute, the values are stored in a postgres domain over the
+/// type of the field, that has the name of the struct in snake case. The
+/// `#[pg_type(check = "...")]` attribute adds a check constraint to the domain.
+///
+/// ```
+/// # use ergol::prelude::*;
+/// # use ergol::tokio;
+/// #[derive(PgType, Debug, PartialEq)]
+/// pub struct Nickname(String);
+///
+/// #[derive(PgType, Debug, PartialEq)]
+/// #[pg_type(check = "VALUE LIKE '%@%'")]
+/// pub struct Email(String);
+///
+/// #[ergol]
+/// pub struct Member {
+///     #[id] pub id: i32,
+///     pub nickname: Nickname,
+///     pub email: Email,
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), ergol::Error> {
+/// #     let (client, connection) = ergol::connect(
+/// #         "host=localhost user=ergol password=ergol dbname=ergol",
+/// #         ergol::tokio_postgres::NoTls,
+/// #     )
+/// #     .await?;
+/// #     tokio::spawn(async move {
+/// #         if let Err(e) = connection.await {
+/// #             eprintln!("connection error: {}", e);
+/// #         }
+/// #     });
+/// # ergol::drop_all(&client).await?;
+/// # ergol::create_all(&client).await?;
+/// let email = Email(String::from("thomas@example.com"));
+/// Member::create(Nickname(String::from("thomas")), email).save(&client).await?;
+///
+/// let members = Member::select()
+///     .filter(member::nickname::eq(Nickname(String::from("thomas"))))
+///     .execute(&client)
+///     .await?;
+/// assert_eq!(members[0].email, Email(String::from("thomas@example.com")));
+///
+/// // The values that don't satisfy the check of the domain are rejected.
+/// let email = Email(String::from("thomas"));
+/// assert!(Member::create(Nickname(String::from("nicolas")), email).save(&client).await.is_err());
+/// # Ok(())
+/// # }
+/// ```
+pub use ergol_proc_macro::PgType;
+
+/// Creates a query written in SQL like `ergol::raw`, whose named placeholders and columns are
+/// checked at compile time.
+///
+/// The first argument is the type of the rows, the second one is the query, and the others give
+/// the values of the named placeholders of the query, e.g. `:age`. The columns of the query are
+/// written between braces, e.g. `{age}` for a field of the first struct of the rows or
+/// `{Post.title}` for a field of another struct, and the macro replaces them with their names,
+/// qualified with the name of their table for the second form.
+///
+/// ```
+/// # use ergol::prelude::*;
+/// # use ergol::tokio;
+/// #[ergol]
+/// pub struct Reader {
+///     #[id] pub id: i32,
+///     pub name: String,
+///     #[column(name = "years")] pub age: i32,
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), ergol::Error> {
+/// #     let (client, connection) = ergol::connect(
+/// #         "host=localhost user=ergol password=ergol dbname=ergol",
+/// #         ergol::tokio_postgres::NoTls,
+/// #     )
+/// #     .await?;
+/// #     tokio::spawn(async move {
+/// #         if let Err(e) = connection.await {
+/// #             eprintln!("connection error: {}", e);
+/// #         }
+/// #     });
+/// # ergol::drop_all(&client).await?;
+/// # ergol::create_all(&client).await?;
+/// Reader::create("alice", 32).save(&client).await?;
+/// Reader::create("bob", 17).save(&client).await?;
+///
+/// let readers = ergol::sql!(
+///     Reader,
+///     "SELECT * FROM readers WHERE {age} >= :min AND {name} <> :name ORDER BY {Reader.age}",
+///     min = 18,
+///     name = "bob",
+/// )
+/// .execute(&client)
+/// .await?;
+///
+/// assert_eq!(readers.len(), 1);
+/// assert_eq!(readers[0].name, "alice");
+/// # Ok(())
+/// # }
+/// ```
+///
+/// An unknown column, or a placeholder without value, does not compile:
+///
+/// ```compile_fail
+/// # use ergol::prelude::*;
+/// # #[ergol]
+/// # pub struct Reader {
+/// #     #[id] pub id: i32,
+/// #     pub name: String,
+/// # }
+/// let query = ergol::sql!(Reader, "SELECT * FROM readers WHERE {email} = :email");
+/// ```
+pub use ergol_proc_macro::sql;
+
 /// The prelude contains the macros and usefull traits.
 pub mod prelude {
     pub use crate::pg::Pg;
     pub use crate::query::Query;
-    pub use crate::{ergol, Ergol, PgEnum, Queryable, ToTable};
+    pub use crate::{ergol, Ergol, PgEnum, PgJson, PgType, Queryable, ToTable};
 }
 
-use tokio_postgres::{tls::MakeTlsConnect, Connection, Error, Socket};
+use tokio_postgres::{tls::MakeTlsConnect, Connection, Socket};
 
 /// Trait.
 pub trait Queryable<T: tokio_postgres::GenericClient> {
@@ -163,7 +444,7 @@ impl Queryable<tokio_postgres::Client> for Ergol {
 
 impl Ergol {
     /// Returns a transaction.
-    pub async fn transaction<'a>(&'a mut self) -> Result<Transaction<'a>, tokio_postgres::Error> {
+    pub async fn transaction<'a>(&'a mut self) -> Result<Transaction<'a>, Error> {
         Ok(Transaction {
             inner: self.client.transaction().await?,
         })
@@ -183,12 +464,149 @@ impl<'a> Queryable<tokio_postgres::Transaction<'a>> for Transaction<'a> {
 }
 
 impl<'a> Transaction<'a> {
-    pub async fn commit(self) -> Result<(), tokio_postgres::Error> {
-        self.inner.commit().await
+    pub async fn commit(self) -> Result<(), Error> {
+        Ok(self.inner.commit().await?)
+    }
+
+    pub async fn rollback(self) -> Result<(), Error> {
+        Ok(self.inner.rollback().await?)
+    }
+
+    /// Runs the next queries of the transaction in the schema of a tenant.
+    ///
+    /// The queries of the scope run in a savepoint of the transaction, which is rolled back if the
+    /// scope is dropped without being released.
+    pub async fn scoped<'b>(&'b mut self, schema: &str) -> Result<TenantScoped<'b>, Error> {
+        TenantScoped::new(self.inner.transaction().await?, schema).await
+    }
+}
+
+impl Ergol {
+    /// Runs the next queries of the connection in the schema of a tenant.
+    ///
+    /// The queries of the scope run in a transaction, which is rolled back if the scope is dropped
+    /// without being released.
+    pub async fn scoped<'a>(&'a mut self, schema: &str) -> Result<TenantScoped<'a>, Error> {
+        TenantScoped::new(self.client.transaction().await?, schema).await
+    }
+}
+
+/// A queryable whose queries run in the schema of a tenant.
+///
+/// It sets the `search_path` to the schema of the tenant, followed by the `public` schema, so that
+/// the tables of structs without a `schema` argument are looked up in the schema of the tenant.
+///
+/// The search path is only set for the transaction of the scope, which borrows its connection
+/// mutably: no other query can run on the connection while the scope is alive, and the search path
+/// is restored when the scope is released or dropped.
+///
+/// ```
+/// # use ergol::prelude::*;
+/// # use ergol::tokio;
+/// #[ergol]
+/// pub struct Memo {
+///     #[id] pub id: i32,
+///     pub body: String,
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), ergol::Error> {
+/// #     let (mut client, connection) = ergol::connect(
+/// #         "host=localhost user=ergol password=ergol dbname=ergol",
+/// #         ergol::tokio_postgres::NoTls,
+/// #     )
+/// #     .await?;
+/// #     tokio::spawn(async move {
+/// #         if let Err(e) = connection.await {
+/// #             eprintln!("connection error: {}", e);
+/// #         }
+/// #     });
+/// for tenant in ["memo_tenant_a", "memo_tenant_b"] {
+///     let query = format!("DROP SCHEMA IF EXISTS {0} CASCADE; CREATE SCHEMA {0};", tenant);
+///     client.client.batch_execute(&query).await?;
+///
+///     let db = client.scoped(tenant).await?;
+///     Memo::create_table().execute(&db).await?;
+///     Memo::create(format!("memo of {}", tenant)).save(&db).await?;
+///     db.release().await?;
+/// }
+///
+/// // Each tenant only reads its own rows.
+/// for tenant in ["memo_tenant_a", "memo_tenant_b"] {
+///     let db = client.scoped(tenant).await?;
+///     let memos = Memo::select().execute(&db).await?;
+///     assert_eq!(memos.len(), 1);
+///     assert_eq!(memos[0].body, format!("memo of {}", tenant));
+///     db.release().await?;
+/// }
+///
+/// // A scope that is dropped without being released is rolled back, with its search path.
+/// {
+///     let db = client.scoped("memo_tenant_a").await?;
+///     Memo::create("lost memo").save(&db).await?;
+/// }
+///
+/// let path = client.client.query_one("SHOW search_path;", &[]).await?;
+/// assert!(!path.get::<_, String>(0).contains("memo_tenant_a"));
+///
+/// let db = client.scoped("memo_tenant_a").await?;
+/// assert_eq!(Memo::select().execute(&db).await?.len(), 1);
+/// db.release().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct TenantScoped<'a> {
+    /// The transaction in which the search path has been set.
+    transaction: tokio_postgres::Transaction<'a>,
+
+    /// The search path before the scope.
+    previous: String,
+
+    /// The schema of the tenant.
+    schema: String,
+}
+
+impl<'a> TenantScoped<'a> {
+    /// Sets the search path of the transaction to the schema.
+    async fn new(
+        transaction: tokio_postgres::Transaction<'a>,
+        schema: &str,
+    ) -> Result<TenantScoped<'a>, Error> {
+        let path = format!("\"{}\", public", schema.replace('"', "\"\""));
+        let row = transaction
+            .query_one(
+                "SELECT current_setting('search_path'), set_config('search_path', $1, true);",
+                &[&path],
+            )
+            .await?;
+
+        Ok(TenantScoped {
+            transaction,
+            previous: row.try_get(0)?,
+            schema: schema.to_string(),
+        })
+    }
+
+    /// Returns the schema of the tenant.
+    pub fn schema(&self) -> &str {
+        &self.schema
     }
 
-    pub async fn rollback(self) -> Result<(), tokio_postgres::Error> {
-        self.inner.rollback().await
+    /// Commits the queries of the scope, and restores the search path.
+    pub async fn release(self) -> Result<(), Error> {
+        self.transaction
+            .query(
+                "SELECT set_config('search_path', $1, true);",
+                &[&self.previous],
+            )
+            .await?;
+        Ok(self.transaction.commit().await?)
+    }
+}
+
+impl<'a> Queryable<tokio_postgres::Transaction<'a>> for TenantScoped<'a> {
+    fn client(&self) -> &tokio_postgres::Transaction<'a> {
+        &self.transaction
     }
 }
 
@@ -196,15 +614,15 @@ impl<'a> Transaction<'a> {
 pub async fn connect<T: MakeTlsConnect<Socket>>(
     config: &str,
     tls: T,
-) -> Result<(Ergol, Connection<Socket, T::Stream>), Error> {
+) -> Result<(Ergol, Connection<Socket, T::Stream>), tokio_postgres::Error> {
     let (a, b) = tokio_postgres::connect(config, tls).await?;
     Ok((Ergol { client: a }, b))
 }
 
 #[cfg(feature = "with-rocket")]
 pub mod pool {
-    use crate::tokio_postgres::NoTls;
-    use crate::{connect, Ergol, Error};
+    use crate::tokio_postgres::{Error, NoTls};
+    use crate::{connect, Ergol};
     use async_trait::async_trait;
 
     /// For dealing with database connection pools.
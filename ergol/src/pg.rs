@@ -1,9 +1,24 @@
 //! This module contains the types for postgres.
 
+use std::convert::TryFrom;
+use std::error::Error;
+use std::ops::Bound;
+use std::time::Duration;
+
+use bytes::{Buf, BufMut, BytesMut};
+
+use tokio_postgres::types::{to_sql_checked, FromSql, IsNull, Kind, ToSql, Type};
+
 /// Any type that can be stored in a database should implement this trait.
 pub trait Pg {
     /// Returns the potgres type corresponding to the type.
     fn ty() -> String;
+
+    /// Returns the key of the json field that holds the name of the variant, for the types stored
+    /// as internally tagged json.
+    fn tag() -> Option<&'static str> {
+        None
+    }
 }
 
 impl Pg for String {
@@ -12,6 +27,18 @@ impl Pg for String {
     }
 }
 
+impl Pg for i8 {
+    fn ty() -> String {
+        "\"char\" NOT NULL".to_owned()
+    }
+}
+
+impl Pg for i16 {
+    fn ty() -> String {
+        "SMALLINT NOT NULL".to_owned()
+    }
+}
+
 impl Pg for i32 {
     fn ty() -> String {
         "INT NOT NULL".to_owned()
@@ -24,6 +51,12 @@ impl Pg for i64 {
     }
 }
 
+impl Pg for u32 {
+    fn ty() -> String {
+        "OID NOT NULL".to_owned()
+    }
+}
+
 impl Pg for bool {
     fn ty() -> String {
         "BOOL NOT NULL".to_owned()
@@ -44,12 +77,512 @@ impl Pg for f64 {
 
 impl<T: Pg + Send> Pg for Option<T> {
     fn ty() -> String {
+        // The NOT NULL is not always at the end, e.g. for relations.
         let current = T::ty();
-        debug_assert!(current.ends_with(" NOT NULL"));
-        return current[0..current.len() - 9].to_owned();
+        debug_assert!(current.contains(" NOT NULL"));
+        current.replacen(" NOT NULL", "", 1)
+    }
+
+    fn tag() -> Option<&'static str> {
+        T::tag()
+    }
+}
+
+/// The arrays are stored as postgres arrays, and their columns have the `contains` (`@>`),
+/// `overlaps` (`&&`) and `any` (`= ANY`) filters.
+///
+/// ```
+/// # use ergol::prelude::*;
+/// # use ergol::tokio;
+/// #[ergol]
+/// pub struct Story {
+///     #[id] pub id: i32,
+///     pub title: String,
+///     pub tags: Vec<String>,
+///     pub scores: Vec<i32>,
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), ergol::Error> {
+/// #     let (client, connection) = ergol::connect(
+/// #         "host=localhost user=ergol password=ergol dbname=ergol",
+/// #         ergol::tokio_postgres::NoTls,
+/// #     )
+/// #     .await?;
+/// #     tokio::spawn(async move {
+/// #         if let Err(e) = connection.await {
+/// #             eprintln!("connection error: {}", e);
+/// #         }
+/// #     });
+/// # ergol::drop_all(&client).await?;
+/// # ergol::create_all(&client).await?;
+/// let tags = |x: &[&str]| x.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+/// Story::create("ergol", tags(&["rust", "orm"]), vec![1, 2]).save(&client).await?;
+/// Story::create("diesel", tags(&["rust", "sql"]), vec![3]).save(&client).await?;
+/// Story::create("hibernate", tags(&["java", "orm"]), vec![]).save(&client).await?;
+///
+/// let rust_orms = Story::select()
+///     .filter(story::tags::contains(tags(&["rust", "orm"])))
+///     .execute(&client)
+///     .await?;
+/// assert_eq!(rust_orms.len(), 1);
+/// assert_eq!(rust_orms[0].title, "ergol");
+///
+/// let sql_or_java = Story::select()
+///     .filter(story::tags::overlaps(tags(&["sql", "java"])))
+///     .execute(&client)
+///     .await?;
+/// assert_eq!(sql_or_java.len(), 2);
+///
+/// let scored_three = Story::select()
+///     .filter(story::scores::any(3))
+///     .execute(&client)
+///     .await?;
+/// assert_eq!(scored_three.len(), 1);
+/// assert_eq!(scored_three[0].title, "diesel");
+/// # Ok(())
+/// # }
+/// ```
+impl<T: Pg> Pg for Vec<T> {
+    fn ty() -> String {
+        // The elements of an array can always be null in postgres.
+        format!("{}[] NOT NULL", T::ty().replacen(" NOT NULL", "", 1))
+    }
+}
+
+impl Pg for Vec<u8> {
+    fn ty() -> String {
+        "BYTEA NOT NULL".to_owned()
+    }
+}
+
+impl Pg for std::net::IpAddr {
+    fn ty() -> String {
+        "INET NOT NULL".to_owned()
+    }
+}
+
+/// A time interval, stored as an `INTERVAL`.
+///
+/// Like the intervals of postgres, it counts the months, the days and the time apart, since the
+/// length of a month or a day depends on the date it is added to, and each of them can be
+/// negative. It can be created from a `Duration`, and converted back into a `Duration` when it has
+/// no months and is not negative, a day being read as 24 hours.
+///
+/// ```
+/// # use ergol::prelude::*;
+/// # use ergol::tokio;
+/// use std::convert::TryFrom;
+/// use std::time::Duration;
+///
+/// use ergol::pg::Interval;
+///
+/// #[ergol]
+/// pub struct Timer {
+///     #[id] pub id: i32,
+///     pub every: ergol::pg::Interval,
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), ergol::Error> {
+/// #     let (client, connection) = ergol::connect(
+/// #         "host=localhost user=ergol password=ergol dbname=ergol",
+/// #         ergol::tokio_postgres::NoTls,
+/// #     )
+/// #     .await?;
+/// #     tokio::spawn(async move {
+/// #         if let Err(e) = connection.await {
+/// #             eprintln!("connection error: {}", e);
+/// #         }
+/// #     });
+/// # ergol::drop_all(&client).await?;
+/// # ergol::create_all(&client).await?;
+/// let hour = Interval::try_from(Duration::from_secs(3600)).unwrap();
+/// let timer = Timer::create(hour).save(&client).await?;
+/// let timer = Timer::get_by_id(timer.id, &client).await?.unwrap();
+/// assert_eq!(timer.every.to_duration(), Some(Duration::from_secs(3600)));
+///
+/// // A month minus a day can not be a duration, but it is read as it was written.
+/// let odd = Interval::new(1, -1, 0);
+/// let timer = Timer::create(odd).save(&client).await?;
+/// let timer = Timer::get_by_id(timer.id, &client).await?.unwrap();
+/// assert_eq!(timer.every, odd);
+/// assert_eq!(timer.every.to_duration(), None);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Interval {
+    /// The number of months.
+    pub months: i32,
+
+    /// The number of days.
+    pub days: i32,
+
+    /// The number of microseconds.
+    pub microseconds: i64,
+}
+
+impl Interval {
+    /// Creates an interval from its months, its days and its microseconds.
+    pub fn new(months: i32, days: i32, microseconds: i64) -> Interval {
+        Interval {
+            months,
+            days,
+            microseconds,
+        }
+    }
+
+    /// Converts the interval into a duration, if it has no months and is not negative.
+    pub fn to_duration(&self) -> Option<Duration> {
+        if self.months != 0 {
+            return None;
+        }
+
+        let day = 24 * 3600 * 1_000_000;
+        let micros = i128::from(self.microseconds) + i128::from(self.days) * day;
+        Some(Duration::from_micros(u64::try_from(micros).ok()?))
+    }
+}
+
+impl TryFrom<Duration> for Interval {
+    type Error = std::num::TryFromIntError;
+
+    /// Creates an interval of microseconds from a duration, which fails if the duration does not
+    /// fit in 64 bits of microseconds.
+    fn try_from(duration: Duration) -> Result<Interval, Self::Error> {
+        Ok(Interval::new(0, 0, i64::try_from(duration.as_micros())?))
+    }
+}
+
+impl Pg for Interval {
+    fn ty() -> String {
+        "INTERVAL NOT NULL".to_owned()
+    }
+}
+
+impl ToSql for Interval {
+    fn to_sql(&self, _: &Type, out: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        // The binary format of an interval is the time in microseconds, the days and the months.
+        out.put_i64(self.microseconds);
+        out.put_i32(self.days);
+        out.put_i32(self.months);
+        Ok(IsNull::No)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        *ty == Type::INTERVAL
+    }
+
+    to_sql_checked!();
+}
+
+impl<'a> FromSql<'a> for Interval {
+    fn from_sql(_: &Type, mut raw: &'a [u8]) -> Result<Interval, Box<dyn Error + Sync + Send>> {
+        if raw.len() != 16 {
+            return Err("invalid buffer size for an interval".into());
+        }
+
+        let microseconds = raw.get_i64();
+        let days = raw.get_i32();
+        let months = raw.get_i32();
+        Ok(Interval::new(months, days, microseconds))
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        *ty == Type::INTERVAL
+    }
+}
+
+/// Any type that can be the bounds of a range.
+pub trait RangeSubtype: Pg {
+    /// Returns the postgres range type whose bounds have this type.
+    fn range_ty() -> &'static str;
+}
+
+impl RangeSubtype for i32 {
+    fn range_ty() -> &'static str {
+        "INT4RANGE"
+    }
+}
+
+impl RangeSubtype for i64 {
+    fn range_ty() -> &'static str {
+        "INT8RANGE"
+    }
+}
+
+/// A range of values, stored as a postgres range type, e.g. `INT4RANGE` or `TSTZRANGE`.
+///
+/// The ranges of integers and dates are normalized by postgres, so that the range `[1, 3]` is read
+/// as `[1, 4)`, and a range without values is read as `Range::Empty`.
+///
+/// The columns of a range have the `contains` (`@>`), `overlaps` (`&&`) and `adjacent` (`-|-`)
+/// filters, and the `exclude(...)` option of a struct prevents the ranges of two rows from
+/// overlapping. An exclusion constraint that compares other columns with `=` needs the
+/// `btree_gist` extension.
+///
+/// ```
+/// # use ergol::prelude::*;
+/// # use ergol::tokio;
+/// use ergol::pg::Range;
+///
+/// #[ergol(exclude(using = "gist", room WITH =, hours WITH &&))]
+/// pub struct Booking {
+///     #[id] pub id: i32,
+///     pub room: i32,
+///     pub hours: Range<i32>,
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), ergol::Error> {
+/// #     let (client, connection) = ergol::connect(
+/// #         "host=localhost user=ergol password=ergol dbname=ergol",
+/// #         ergol::tokio_postgres::NoTls,
+/// #     )
+/// #     .await?;
+/// #     tokio::spawn(async move {
+/// #         if let Err(e) = connection.await {
+/// #             eprintln!("connection error: {}", e);
+/// #         }
+/// #     });
+/// # ergol::drop_all(&client).await?;
+/// client.client().batch_execute("CREATE EXTENSION IF NOT EXISTS btree_gist").await?;
+/// # ergol::create_all(&client).await?;
+/// Booking::create(1, Range::from(9..12)).save(&client).await?;
+/// Booking::create(2, Range::from(10..11)).save(&client).await?;
+///
+/// // The room 1 is already booked from 9 to 12.
+/// assert!(Booking::create(1, Range::from(11..13)).save(&client).await.is_err());
+///
+/// let afternoon = Booking::select()
+///     .filter(booking::hours::adjacent(Range::from(12..18)))
+///     .execute(&client)
+///     .await?;
+/// assert_eq!(afternoon.len(), 1);
+/// assert_eq!(afternoon[0].hours, Range::from(9..12));
+///
+/// let at_ten = Booking::select()
+///     .filter(booking::hours::contains(Range::from(10..=10)))
+///     .execute(&client)
+///     .await?;
+/// assert_eq!(at_ten.len(), 2);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Range<T> {
+    /// The range that contains no values.
+    Empty,
+
+    /// The values between a lower bound and an upper bound.
+    Between(Bound<T>, Bound<T>),
+}
+
+impl<T> Range<T> {
+    /// Creates the range between two bounds.
+    pub fn new(lower: Bound<T>, upper: Bound<T>) -> Range<T> {
+        Range::Between(lower, upper)
+    }
+}
+
+impl<T> From<std::ops::Range<T>> for Range<T> {
+    fn from(range: std::ops::Range<T>) -> Range<T> {
+        Range::Between(Bound::Included(range.start), Bound::Excluded(range.end))
+    }
+}
+
+impl<T> From<std::ops::RangeInclusive<T>> for Range<T> {
+    fn from(range: std::ops::RangeInclusive<T>) -> Range<T> {
+        let (start, end) = range.into_inner();
+        Range::Between(Bound::Included(start), Bound::Included(end))
+    }
+}
+
+impl<T: RangeSubtype> Pg for Range<T> {
+    fn ty() -> String {
+        format!("{} NOT NULL", T::range_ty())
+    }
+}
+
+// The flags of the binary format of ranges.
+const RANGE_EMPTY: u8 = 0x01;
+const RANGE_LOWER_INCLUSIVE: u8 = 0x02;
+const RANGE_UPPER_INCLUSIVE: u8 = 0x04;
+const RANGE_LOWER_INFINITE: u8 = 0x08;
+const RANGE_UPPER_INFINITE: u8 = 0x10;
+
+/// Returns the type of the bounds of a range type.
+fn range_subtype(ty: &Type) -> Option<&Type> {
+    match ty.kind() {
+        Kind::Range(subtype) => Some(subtype),
+        Kind::Domain(base) => range_subtype(base),
+        _ => None,
+    }
+}
+
+/// Writes a finite bound of a range, as its length followed by its value.
+fn write_bound<T: ToSql>(
+    value: &T,
+    ty: &Type,
+    out: &mut BytesMut,
+) -> Result<(), Box<dyn Error + Sync + Send>> {
+    let start = out.len();
+    out.put_i32(0);
+
+    if let IsNull::Yes = value.to_sql(ty, out)? {
+        return Err("the bounds of a range can not be null".into());
+    }
+
+    let len = i32::try_from(out.len() - start - 4)?;
+    out[start..start + 4].copy_from_slice(&len.to_be_bytes());
+    Ok(())
+}
+
+/// Reads a finite bound of a range.
+fn read_bound<'a, T: FromSql<'a>>(
+    ty: &Type,
+    raw: &mut &'a [u8],
+) -> Result<T, Box<dyn Error + Sync + Send>> {
+    if raw.len() < 4 {
+        return Err("invalid buffer size for a range".into());
+    }
+
+    let len = usize::try_from(raw.get_i32())?;
+    if raw.len() < len {
+        return Err("invalid buffer size for a range".into());
     }
+
+    let (value, rest) = raw.split_at(len);
+    *raw = rest;
+    T::from_sql(ty, value)
 }
 
+impl<T: ToSql> ToSql for Range<T> {
+    fn to_sql(
+        &self,
+        ty: &Type,
+        out: &mut BytesMut,
+    ) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        let subtype = range_subtype(ty).ok_or("expected a range type")?;
+
+        let (lower, upper) = match self {
+            Range::Empty => {
+                out.put_u8(RANGE_EMPTY);
+                return Ok(IsNull::No);
+            }
+            Range::Between(lower, upper) => (lower, upper),
+        };
+
+        let mut flags = 0;
+        match lower {
+            Bound::Included(_) => flags |= RANGE_LOWER_INCLUSIVE,
+            Bound::Excluded(_) => (),
+            Bound::Unbounded => flags |= RANGE_LOWER_INFINITE,
+        }
+        match upper {
+            Bound::Included(_) => flags |= RANGE_UPPER_INCLUSIVE,
+            Bound::Excluded(_) => (),
+            Bound::Unbounded => flags |= RANGE_UPPER_INFINITE,
+        }
+        out.put_u8(flags);
+
+        for bound in [lower, upper] {
+            if let Bound::Included(value) | Bound::Excluded(value) = bound {
+                write_bound(value, subtype, out)?;
+            }
+        }
+
+        Ok(IsNull::No)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        range_subtype(ty).is_some_and(T::accepts)
+    }
+
+    to_sql_checked!();
+}
+
+impl<'a, T: FromSql<'a>> FromSql<'a> for Range<T> {
+    fn from_sql(ty: &Type, mut raw: &'a [u8]) -> Result<Range<T>, Box<dyn Error + Sync + Send>> {
+        let subtype = range_subtype(ty).ok_or("expected a range type")?;
+
+        if raw.is_empty() {
+            return Err("invalid buffer size for a range".into());
+        }
+
+        let flags = raw.get_u8();
+        if flags & RANGE_EMPTY != 0 {
+            return Ok(Range::Empty);
+        }
+
+        let lower = if flags & RANGE_LOWER_INFINITE != 0 {
+            Bound::Unbounded
+        } else if flags & RANGE_LOWER_INCLUSIVE != 0 {
+            Bound::Included(read_bound(subtype, &mut raw)?)
+        } else {
+            Bound::Excluded(read_bound(subtype, &mut raw)?)
+        };
+
+        let upper = if flags & RANGE_UPPER_INFINITE != 0 {
+            Bound::Unbounded
+        } else if flags & RANGE_UPPER_INCLUSIVE != 0 {
+            Bound::Included(read_bound(subtype, &mut raw)?)
+        } else {
+            Bound::Excluded(read_bound(subtype, &mut raw)?)
+        };
+
+        Ok(Range::Between(lower, upper))
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        range_subtype(ty).is_some_and(T::accepts)
+    }
+}
+
+/// Any type that can be used as the primary key of a table.
+///
+/// The columns that reference a table have the same postgres type as its primary key.
+pub trait Key: Pg + ToSql + for<'a> FromSql<'a> + Clone + std::fmt::Debug + Send + Sync {}
+
+impl Key for i32 {}
+
+impl Key for i64 {}
+
+impl Key for String {}
+
+#[cfg(feature = "with-uuid-0_8")]
+impl Key for uuid_0_8::Uuid {}
+
+#[cfg(feature = "with-uuid-1")]
+impl Key for uuid_1::Uuid {}
+
+/// Types that hold a point in time, and that can be used for the `#[created_at]` and
+/// `#[updated_at]` columns.
+pub trait Timestamp: Pg {}
+
+/// Types of the program that derive `PgEnum`, `PgJson` or `PgType`, and whose columns have the
+/// type created by the derive.
+///
+/// The columns of the other types of the program are refused when the struct is compiled:
+///
+/// ```compile_fail,E0277
+/// # use ergol::prelude::*;
+/// #[derive(Debug)]
+/// pub struct Celsius(f64);
+///
+/// #[ergol]
+/// pub struct Reading {
+///     #[id] pub id: i32,
+///     pub temperature: Option<Celsius>,
+/// }
+/// ```
+#[diagnostic::on_unimplemented(
+    message = "the type `{Self}` can not be stored",
+    note = "the types of the program must derive `PgEnum`, `PgJson` or `PgType`"
+)]
+pub trait Defined: Pg {}
+
 #[allow(unused)]
 macro_rules! impl_pg {
     ($ty: ty, $e: expr) => {
@@ -69,9 +602,21 @@ macro_rules! impl_pg {
     };
 }
 
+/// A value stored as `JSONB`, which is also the type of the columns of `Json<T>`.
+#[cfg(feature = "with-serde_json-1")]
+pub type Jsonb<T> = tokio_postgres::types::Json<T>;
+
+#[rustfmt::skip]
+#[cfg(feature = "with-serde_json-1")]
+impl_pg!(tokio_postgres::types::Json<T>, T, "JSONB NOT NULL");
+
 #[rustfmt::skip]
 #[cfg(feature = "with-serde_json-1")]
-impl_pg!(tokio_postgres::types::Json<T>, T, "JSON NOT NULL");
+impl_pg!(serde_json::Value, "JSONB NOT NULL");
+
+#[rustfmt::skip]
+#[cfg(feature = "with-rust_decimal-1")]
+impl_pg!(rust_decimal::Decimal, "NUMERIC NOT NULL");
 
 #[rustfmt::skip]
 #[cfg(feature = "with-bit-vec-0_6")]
@@ -156,3 +701,86 @@ impl_pg!(time_0_3::Date, "DATE NOT NULL");
 #[rustfmt::skip]
 #[cfg(feature = "with-time-0_3")]
 impl_pg!(time_0_3::Time, "TIME NOT NULL");
+
+#[cfg(feature = "with-chrono-0_4")]
+impl Timestamp for chrono::NaiveDateTime {}
+
+#[cfg(feature = "with-chrono-0_4")]
+impl Timestamp for chrono::DateTime<chrono::Utc> {}
+
+#[cfg(feature = "with-chrono-0_4")]
+impl Timestamp for chrono::DateTime<chrono::Local> {}
+
+#[cfg(feature = "with-chrono-0_4")]
+impl Timestamp for chrono::DateTime<chrono::FixedOffset> {}
+
+#[cfg(feature = "with-time-0_2")]
+impl Timestamp for time_0_2::PrimitiveDateTime {}
+
+#[cfg(feature = "with-time-0_2")]
+impl Timestamp for time_0_2::OffsetDateTime {}
+
+#[cfg(feature = "with-time-0_3")]
+impl Timestamp for time_0_3::PrimitiveDateTime {}
+
+#[cfg(feature = "with-time-0_3")]
+impl Timestamp for time_0_3::OffsetDateTime {}
+
+#[allow(unused)]
+macro_rules! impl_range_subtype {
+    ($ty: ty, $e: expr) => {
+        impl RangeSubtype for $ty {
+            fn range_ty() -> &'static str {
+                $e
+            }
+        }
+    };
+}
+
+#[rustfmt::skip]
+#[cfg(feature = "with-rust_decimal-1")]
+impl_range_subtype!(rust_decimal::Decimal, "NUMRANGE");
+
+#[rustfmt::skip]
+#[cfg(feature = "with-chrono-0_4")]
+impl_range_subtype!(chrono::NaiveDateTime, "TSRANGE");
+
+#[rustfmt::skip]
+#[cfg(feature = "with-chrono-0_4")]
+impl_range_subtype!(chrono::DateTime<chrono::Utc>, "TSTZRANGE");
+
+#[rustfmt::skip]
+#[cfg(feature = "with-chrono-0_4")]
+impl_range_subtype!(chrono::DateTime<chrono::Local>, "TSTZRANGE");
+
+#[rustfmt::skip]
+#[cfg(feature = "with-chrono-0_4")]
+impl_range_subtype!(chrono::DateTime<chrono::FixedOffset>, "TSTZRANGE");
+
+#[rustfmt::skip]
+#[cfg(feature = "with-chrono-0_4")]
+impl_range_subtype!(chrono::NaiveDate, "DATERANGE");
+
+#[rustfmt::skip]
+#[cfg(feature = "with-time-0_2")]
+impl_range_subtype!(time_0_2::PrimitiveDateTime, "TSRANGE");
+
+#[rustfmt::skip]
+#[cfg(feature = "with-time-0_2")]
+impl_range_subtype!(time_0_2::OffsetDateTime, "TSTZRANGE");
+
+#[rustfmt::skip]
+#[cfg(feature = "with-time-0_2")]
+impl_range_subtype!(time_0_2::Date, "DATERANGE");
+
+#[rustfmt::skip]
+#[cfg(feature = "with-time-0_3")]
+impl_range_subtype!(time_0_3::PrimitiveDateTime, "TSRANGE");
+
+#[rustfmt::skip]
+#[cfg(feature = "with-time-0_3")]
+impl_range_subtype!(time_0_3::OffsetDateTime, "TSTZRANGE");
+
+#[rustfmt::skip]
+#[cfg(feature = "with-time-0_3")]
+impl_range_subtype!(time_0_3::Date, "DATERANGE");
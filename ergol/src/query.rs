@@ -3,10 +3,10 @@
 use std::future::Future;
 use std::marker::{PhantomData, Sync};
 
-use tokio_postgres::{types::ToSql, Error, GenericClient};
+use tokio_postgres::{types::ToSql, GenericClient};
 
 use crate::prelude::*;
-use crate::Queryable;
+use crate::{Error, Queryable};
 
 /// Any query should implement this trait.
 pub trait Query {
@@ -34,6 +34,60 @@ pub enum Filter {
         operator: Operator,
     },
 
+    /// A filter from a binary operator on a field of the json values of a column.
+    Json {
+        /// The name of the column.
+        column: &'static str,
+
+        /// The key of the field.
+        key: String,
+
+        /// Whether the field is compared as text (`->>`) or as json (`->`).
+        text: bool,
+
+        /// The value for the filter.
+        value: Box<dyn ToSql + Send + Sync + 'static>,
+
+        /// The operator of the filter.
+        operator: Operator,
+    },
+
+    /// A filter on the name of the variant of a value stored as json.
+    Tag {
+        /// The name of the column.
+        column: &'static str,
+
+        /// The key of the field that holds the name of the variant, or None if the name of the
+        /// variant is the key of the value, or the value itself for the variants without fields.
+        ///
+        /// The key is given by the type of the column when the filter is added to a query.
+        key: Option<&'static str>,
+
+        /// The name of the variant.
+        value: Box<dyn ToSql + Send + Sync + 'static>,
+    },
+
+    /// A filter that keeps the rows whose full text search vector matches a query.
+    Search {
+        /// The name of the generated column that holds the search vectors.
+        column: &'static str,
+
+        /// The text search configuration of the column, e.g. `english`.
+        lang: &'static str,
+
+        /// The query, written with the syntax of web search engines.
+        value: Box<dyn ToSql + Send + Sync + 'static>,
+    },
+
+    /// A filter that keeps the arrays of a column that contain a value.
+    Any {
+        /// The name of the column.
+        column: &'static str,
+
+        /// The value that the array must contain.
+        value: Box<dyn ToSql + Send + Sync + 'static>,
+    },
+
     /// And between two filters.
     And(Box<Filter>, Box<Filter>),
 
@@ -57,6 +111,49 @@ impl Filter {
                 first_index + 1,
                 vec![value.as_ref()],
             ),
+            Filter::Json {
+                column,
+                key,
+                text,
+                value,
+                operator,
+            } => (
+                format!(
+                    "\"{}\" {} ${} {} ${}",
+                    column,
+                    if *text { "->>" } else { "->" },
+                    first_index,
+                    operator.to_str(),
+                    first_index + 1
+                ),
+                first_index + 2,
+                vec![key as &(dyn ToSql + Sync), value.as_ref()],
+            ),
+            Filter::Tag { column, key, value } => (
+                match key {
+                    Some(key) => format!("\"{}\" ->> '{}' = ${}", column, key, first_index),
+                    None => format!("\"{}\" ? ${}", column, first_index),
+                },
+                first_index + 1,
+                vec![value.as_ref()],
+            ),
+            Filter::Search {
+                column,
+                lang,
+                value,
+            } => (
+                format!(
+                    "\"{}\" @@ websearch_to_tsquery('{}', ${})",
+                    column, lang, first_index
+                ),
+                first_index + 1,
+                vec![value.as_ref()],
+            ),
+            Filter::Any { column, value } => (
+                format!("${} = ANY(\"{}\")", first_index, column),
+                first_index + 1,
+                vec![value.as_ref()],
+            ),
             Filter::And(a, b) => {
                 let (a, next, mut args1) = a.to_string(first_index);
                 let (b, next, args2) = b.to_string(next);
@@ -72,6 +169,20 @@ impl Filter {
         }
     }
 
+    /// Sets the keys of the tag filters from the types of the columns of a table.
+    fn with_tags<T: ToTable>(self) -> Filter {
+        match self {
+            Filter::Tag { column, value, .. } => Filter::Tag {
+                column,
+                key: T::column_tag(column),
+                value,
+            },
+            Filter::And(a, b) => a.with_tags::<T>().and(b.with_tags::<T>()),
+            Filter::Or(a, b) => a.with_tags::<T>().or(b.with_tags::<T>()),
+            filter => filter,
+        }
+    }
+
     /// Returns another filter that performs an and between self and other.
     pub fn and(self, other: Filter) -> Filter {
         Filter::And(Box::new(self), Box::new(other))
@@ -83,6 +194,64 @@ impl Filter {
     }
 }
 
+/// A field of the json values of a column, that can be compared to build filters.
+pub struct JsonField {
+    /// The name of the column.
+    pub column: &'static str,
+
+    /// The key of the field.
+    pub key: String,
+
+    /// Whether the field is compared as text (`->>`) or as json (`->`).
+    pub text: bool,
+}
+
+impl JsonField {
+    /// Returns the filter that compares the field with a value.
+    fn filter<T: ToSql + Sync + Send + 'static>(self, value: T, operator: Operator) -> Filter {
+        Filter::Json {
+            column: self.column,
+            key: self.key,
+            text: self.text,
+            value: Box::new(value),
+            operator,
+        }
+    }
+
+    /// Keeps only the results for which the field equals the value passed as parameter.
+    pub fn eq<T: ToSql + Sync + Send + 'static>(self, value: T) -> Filter {
+        self.filter(value, Operator::Eq)
+    }
+
+    /// Keeps only the results for which the field is different from the value passed as
+    /// parameter.
+    pub fn neq<T: ToSql + Sync + Send + 'static>(self, value: T) -> Filter {
+        self.filter(value, Operator::Neq)
+    }
+
+    /// Keeps only the results for which the field is lesser or equals the value passed as
+    /// parameter.
+    pub fn leq<T: ToSql + Sync + Send + 'static>(self, value: T) -> Filter {
+        self.filter(value, Operator::Leq)
+    }
+
+    /// Keeps only the results for which the field is greater or equals the value passed as
+    /// parameter.
+    pub fn geq<T: ToSql + Sync + Send + 'static>(self, value: T) -> Filter {
+        self.filter(value, Operator::Geq)
+    }
+
+    /// Keeps only the results for which the field is lesser than the value passed as parameter.
+    pub fn lt<T: ToSql + Sync + Send + 'static>(self, value: T) -> Filter {
+        self.filter(value, Operator::Lt)
+    }
+
+    /// Keeps only the results for which the field is greater than the value passed as parameter.
+    pub fn gt<T: ToSql + Sync + Send + 'static>(self, value: T) -> Filter {
+        self.filter(value, Operator::Gt)
+    }
+}
+
 /// Decend of ascend.
 #[derive(Copy, Clone)]
 pub enum Order {
@@ -110,10 +279,87 @@ pub struct OrderBy {
 
     /// The type of order.
     pub order: Order,
+
+    /// The full text search query whose rank sorts the results, if the column holds search
+    /// vectors.
+    pub rank: Option<Rank>,
+}
+
+/// The rank of the search vectors of a column for a full text search query.
+///
+/// The fields marked as `#[fulltext]` get a generated column with their search vectors, with a GIN
+/// index, and their columns have a `matches` filter and a `rank` order.
+///
+/// ```
+/// # use ergol::prelude::*;
+/// # use ergol::tokio;
+/// #[ergol]
+/// pub struct Article {
+///     #[id] pub id: i32,
+///     pub title: String,
+///     #[fulltext(lang = "english")] pub body: String,
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), ergol::Error> {
+/// #     let (client, connection) = ergol::connect(
+/// #         "host=localhost user=ergol password=ergol dbname=ergol",
+/// #         ergol::tokio_postgres::NoTls,
+/// #     )
+/// #     .await?;
+/// #     tokio::spawn(async move {
+/// #         if let Err(e) = connection.await {
+/// #             eprintln!("connection error: {}", e);
+/// #         }
+/// #     });
+/// # ergol::drop_all(&client).await?;
+/// # ergol::create_all(&client).await?;
+/// let body = "Databases store rows, and an ORM maps the rows to structs.";
+/// Article::create("Databases", body).save(&client).await?;
+///
+/// let body = "Rust has an ORM for postgres, and the ORM is async.";
+/// Article::create("Rust", body).save(&client).await?;
+///
+/// let articles = Article::select()
+///     .filter(article::body::matches("orm -databases"))
+///     .execute(&client)
+///     .await?;
+/// assert_eq!(articles.len(), 1);
+/// assert_eq!(articles[0].title, "Rust");
+///
+/// let articles = Article::select()
+///     .filter(article::body::matches("orm"))
+///     .order_by(article::body::rank("orm"))
+///     .execute(&client)
+///     .await?;
+/// assert_eq!(articles[0].title, "Rust");
+/// assert_eq!(articles[1].title, "Databases");
+/// # Ok(())
+/// # }
+/// ```
+pub struct Rank {
+    /// The text search configuration of the column, e.g. `english`.
+    pub lang: &'static str,
+
+    /// The query, written with the syntax of web search engines.
+    pub value: Box<dyn ToSql + Send + Sync + 'static>,
+}
+
+/// Which soft deleted rows a query should return.
+#[derive(Copy, Clone)]
+pub enum Deleted {
+    /// Excludes the soft deleted rows.
+    Exclude,
+
+    /// Returns the soft deleted rows along with the others.
+    Include,
+
+    /// Returns only the soft deleted rows.
+    Only,
 }
 
 /// A select query on T.
-pub struct Select<T: ToTable + ?Sized> {
+pub struct Select<T: ToTable> {
     _marker: PhantomData<T>,
 
     /// How many results you want to have.
@@ -127,6 +373,9 @@ pub struct Select<T: ToTable + ?Sized> {
 
     /// A filter.
     filter: Option<Filter>,
+
+    /// Which soft deleted rows to return.
+    deleted: Deleted,
 }
 
 impl<T: ToTable + Sync> Select<T> {
@@ -138,6 +387,7 @@ impl<T: ToTable + Sync> Select<T> {
             offset: None,
             order_by: None,
             filter: None,
+            deleted: Deleted::Exclude,
         }
     }
 
@@ -161,9 +411,27 @@ impl<T: ToTable + Sync> Select<T> {
 
     /// Sets the filter of the select query.
     pub fn filter(mut self, filter: Filter) -> Select<T> {
-        self.filter = Some(filter);
+        self.filter = Some(filter.with_tags::<T>());
         self
     }
+
+    /// Includes the soft deleted rows in the results of the select query.
+    pub fn with_deleted(mut self) -> Select<T> {
+        self.deleted = Deleted::Include;
+        self
+    }
+
+    /// Keeps only the soft deleted rows in the results of the select query.
+    pub fn only_deleted(mut self) -> Select<T> {
+        self.deleted = Deleted::Only;
+        self
+    }
+}
+
+impl<T: ToTable + Sync> Default for Select<T> {
+    fn default() -> Select<T> {
+        Select::new()
+    }
 }
 
 /// The different comparison operators for filters.
@@ -192,6 +460,18 @@ pub enum Operator {
 
     /// String similary to another string.
     SimilarTo,
+
+    /// Array or range that contains all the elements of another array or range.
+    Contains,
+
+    /// Array or range that has elements in common with another array or range.
+    Overlaps,
+
+    /// Range that is adjacent to another range.
+    Adjacent,
+
+    /// Json value that has a key.
+    HasKey,
 }
 
 impl Operator {
@@ -206,6 +486,10 @@ impl Operator {
             Operator::Neq => "!=",
             Operator::Like => "LIKE",
             Operator::SimilarTo => "SIMILAR TO",
+            Operator::Contains => "@>",
+            Operator::Overlaps => "&&",
+            Operator::Adjacent => "-|-",
+            Operator::HasKey => "?",
         }
     }
 }
@@ -218,23 +502,51 @@ impl<T: ToTable + Sync> Query for Select<T> {
         ergol: &Q,
     ) -> Result<Self::Output, Error> {
         let filter = self.filter.as_ref().map(|x| x.to_string(1));
+        let (next, mut args) = match &filter {
+            Some((_, next, args)) => (*next, args.clone()),
+            None => (1, vec![]),
+        };
+
+        let deleted = match (T::soft_delete_name(), self.deleted) {
+            (Some(column), Deleted::Exclude) => Some(format!("\"{}\" IS NULL", column)),
+            (Some(column), Deleted::Only) => Some(format!("\"{}\" IS NOT NULL", column)),
+            (None, Deleted::Only) => Some(String::from("FALSE")),
+            _ => None,
+        };
+
+        let conditions = filter
+            .iter()
+            .map(|(filter, _, _)| filter.clone())
+            .chain(deleted)
+            .collect::<Vec<_>>();
 
         let query = format!(
-            "SELECT * FROM \"{}\"{}{}{}{};",
-            T::table_name(),
-            if let Some((filter, _, _)) = filter.as_ref() {
-                format!(" WHERE {}", filter)
-            } else {
+            "SELECT * FROM {}{}{}{}{};",
+            T::qualified_table_name(),
+            if conditions.is_empty() {
                 String::new()
-            },
-            if let Some(order_by) = self.order_by.as_ref() {
-                format!(
-                    " ORDER BY \"{}\" {}",
-                    order_by.column,
-                    order_by.order.to_str()
-                )
             } else {
-                String::new()
+                format!(" WHERE {}", conditions.join(" AND "))
+            },
+            match self.order_by.as_ref() {
+                Some(OrderBy {
+                    column,
+                    order,
+                    rank: Some(rank),
+                }) => {
+                    args.push(rank.value.as_ref());
+                    format!(
+                        " ORDER BY ts_rank(\"{}\", websearch_to_tsquery('{}', ${})) {}",
+                        column,
+                        rank.lang,
+                        next,
+                        order.to_str()
+                    )
+                }
+                Some(OrderBy { column, order, .. }) => {
+                    format!(" ORDER BY \"{}\" {}", column, order.to_str())
+                }
+                None => String::new(),
             },
             if let Some(limit) = self.limit {
                 format!(" LIMIT {}", limit)
@@ -248,23 +560,155 @@ impl<T: ToTable + Sync> Query for Select<T> {
             }
         );
 
-        if let Some((_, _, args)) = filter {
-            Ok(ergol
-                .client()
-                .query(&query as &str, &args[..])
-                .await?
-                .iter()
-                .map(<T as ToTable>::from_row)
-                .collect::<Vec<_>>())
-        } else {
-            Ok(ergol
-                .client()
-                .query(&query as &str, &[])
-                .await?
-                .iter()
-                .map(<T as ToTable>::from_row)
-                .collect::<Vec<_>>())
+        ergol
+            .client()
+            .query(&query as &str, &args[..])
+            .await?
+            .iter()
+            .map(<T as ToTable>::from_row)
+            .collect()
+    }
+}
+
+/// Any type that can be read from the columns of a row: the structs of the tables, and the tuples of
+/// those structs, whose columns follow each other.
+pub trait FromRow: Sized {
+    /// Returns the number of columns that are read.
+    fn column_count() -> usize;
+
+    /// Reads the columns of a row starting at an offset.
+    fn from_row_with_offset(row: &tokio_postgres::Row, offset: usize) -> Result<Self, Error>;
+}
+
+impl<T: ToTable> FromRow for T {
+    fn column_count() -> usize {
+        <T as ToTable>::column_count()
+    }
+
+    fn from_row_with_offset(row: &tokio_postgres::Row, offset: usize) -> Result<Self, Error> {
+        <T as ToTable>::from_row_with_offset(row, offset)
+    }
+}
+
+macro_rules! impl_from_row_tuple {
+    ($($t: ident),*) => {
+        impl<$($t: ToTable),*> FromRow for ($($t,)*) {
+            fn column_count() -> usize {
+                0 $(+ $t::column_count())*
+            }
+
+            #[allow(unused_assignments)]
+            fn from_row_with_offset(
+                row: &tokio_postgres::Row,
+                mut offset: usize,
+            ) -> Result<Self, Error> {
+                Ok(($(
+                    {
+                        let value = $t::from_row_with_offset(row, offset)?;
+                        offset += $t::column_count();
+                        value
+                    },
+                )*))
+            }
         }
+    };
+}
+
+impl_from_row_tuple!(A, B);
+impl_from_row_tuple!(A, B, C);
+impl_from_row_tuple!(A, B, C, D);
+
+/// A query written in SQL, whose rows are read as structs of tables or tuples of those structs.
+pub struct Raw<'a, T: FromRow> {
+    _marker: PhantomData<T>,
+
+    /// The SQL query, whose parameters are `$1`, `$2`, ...
+    query: String,
+
+    /// The parameters of the query.
+    params: &'a [&'a (dyn ToSql + Sync)],
+}
+
+/// Creates a query written in SQL, whose rows are read as structs of tables or tuples of those
+/// structs.
+///
+/// The query must select all the columns of the tables, in the order of the types, e.g.
+/// `SELECT writers.*, essays.* FROM ...` for `(Writer, Essay)`.
+///
+/// ```
+/// # use ergol::prelude::*;
+/// # use ergol::tokio;
+/// #[ergol]
+/// pub struct Writer {
+///     #[id] pub id: i32,
+///     pub name: String,
+///     pub age: i32,
+/// }
+///
+/// #[ergol]
+/// pub struct Essay {
+///     #[id] pub id: i32,
+///     pub title: String,
+///     #[many_to_one(essays)] pub author: Writer,
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), ergol::Error> {
+/// #     let (client, connection) = ergol::connect(
+/// #         "host=localhost user=ergol password=ergol dbname=ergol",
+/// #         ergol::tokio_postgres::NoTls,
+/// #     )
+/// #     .await?;
+/// #     tokio::spawn(async move {
+/// #         if let Err(e) = connection.await {
+/// #             eprintln!("connection error: {}", e);
+/// #         }
+/// #     });
+/// # ergol::drop_all(&client).await?;
+/// # ergol::create_all(&client).await?;
+/// let alice = Writer::create("alice", 32).save(&client).await?;
+/// Writer::create("bob", 17).save(&client).await?;
+/// Essay::create("Hello", &alice).save(&client).await?;
+///
+/// let adults = ergol::raw::<Writer>("SELECT * FROM writers WHERE age >= $1", &[&18])
+///     .execute(&client)
+///     .await?;
+/// assert_eq!(adults.len(), 1);
+/// assert_eq!(adults[0].name, "alice");
+///
+/// let essays = ergol::raw::<(Essay, Writer)>(
+///     "SELECT essays.*, writers.* FROM essays JOIN writers ON essays.author = writers.id",
+///     &[],
+/// )
+/// .execute(&client)
+/// .await?;
+/// assert_eq!(essays[0].0.title, "Hello");
+/// assert_eq!(essays[0].1.name, "alice");
+/// # Ok(())
+/// # }
+/// ```
+pub fn raw<'a, T: FromRow>(query: &str, params: &'a [&'a (dyn ToSql + Sync)]) -> Raw<'a, T> {
+    Raw {
+        _marker: PhantomData,
+        query: query.to_owned(),
+        params,
+    }
+}
+
+impl<'a, T: FromRow> Query for Raw<'a, T> {
+    type Output = Vec<T>;
+
+    async fn execute<C: GenericClient, Q: Queryable<C>>(
+        self,
+        ergol: &Q,
+    ) -> Result<Self::Output, Error> {
+        ergol
+            .client()
+            .query(&self.query as &str, self.params)
+            .await?
+            .iter()
+            .map(|row| T::from_row_with_offset(row, 0))
+            .collect()
     }
 }
 
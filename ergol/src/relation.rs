@@ -8,13 +8,39 @@ use bytes::BytesMut;
 use tokio_postgres::types::{to_sql_checked, FromSql, IsNull, ToSql, Type};
 use tokio_postgres::GenericClient;
 
-use crate::{pg::Pg, Queryable, ToTable};
+use crate::{
+    pg::{Key, Pg},
+    Error, Queryable, ToTable,
+};
 
 #[cfg(feature = "with-serde")]
-use serde::{
-    de::{self, Visitor},
-    Deserialize, Deserializer, Serialize, Serializer,
-};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Fetches the element of a table from its id, excluding the soft deleted elements unless
+/// `with_deleted` is true.
+async fn fetch<T: ToTable, Q: Queryable<impl GenericClient>>(
+    id: &T::Id,
+    with_deleted: bool,
+    ergol: &Q,
+) -> Result<T, Error>
+where
+    T::Id: Key,
+{
+    let deleted = match T::soft_delete_name() {
+        Some(column) if !with_deleted => format!(" AND \"{}\" IS NULL", column),
+        _ => String::new(),
+    };
+
+    let query = format!(
+        "SELECT * FROM {} WHERE \"{}\" = $1{}",
+        T::qualified_table_name(),
+        T::id_name(),
+        deleted,
+    );
+    let mut rows = ergol.client().query(&query as &str, &[id]).await?;
+    let row = rows.pop().ok_or(Error::NotFound)?;
+    <T as ToTable>::from_row(&row)
+}
 
 /// The different types of relation that tables can have are managed with this trait.
 pub trait Relation<U: ToTable> {
@@ -25,23 +51,38 @@ pub trait Relation<U: ToTable> {
     type Reverse;
 
     /// Convert rows into the reverse type.
-    fn from_rows(rows: Vec<tokio_postgres::Row>) -> Self::Reverse;
+    fn from_rows(rows: Vec<tokio_postgres::Row>) -> Result<Self::Reverse, Error>;
 }
 
 /// A one to one relation ship.
 ///
 /// You should not use this type by yourself, it should be automatically generated by the
 /// `#[one_to_one]` macro attribute.
-#[derive(Debug, Clone, Copy)]
-pub struct OneToOne<T: ToTable> {
+#[derive(Debug)]
+pub struct OneToOne<T: ToTable>
+where
+    T::Id: Key,
+{
     _phantom: PhantomData<T>,
     /// The id of the referenced element.
-    id: i32,
+    id: T::Id,
+}
+
+impl<T: ToTable> Clone for OneToOne<T>
+where
+    T::Id: Key,
+{
+    fn clone(&self) -> OneToOne<T> {
+        OneToOne::new(self.id.clone())
+    }
 }
 
-impl<T: ToTable> OneToOne<T> {
+impl<T: ToTable> OneToOne<T>
+where
+    T::Id: Key,
+{
     /// Creates a one to one relation ship.
-    pub fn new(id: i32) -> OneToOne<T> {
+    pub fn new(id: T::Id) -> OneToOne<T> {
         OneToOne {
             _phantom: PhantomData,
             id,
@@ -49,66 +90,85 @@ impl<T: ToTable> OneToOne<T> {
     }
 
     /// Fetches the referenced element.
-    pub async fn fetch<Q: Queryable<impl GenericClient>>(
+    ///
+    /// Returns `Error::NotFound` if the element was soft deleted.
+    pub async fn fetch<Q: Queryable<impl GenericClient>>(&self, ergol: &Q) -> Result<T, Error> {
+        fetch(&self.id, false, ergol).await
+    }
+
+    /// Fetches the referenced element, even if it was soft deleted.
+    pub async fn fetch_with_deleted<Q: Queryable<impl GenericClient>>(
         &self,
         ergol: &Q,
-    ) -> Result<T, tokio_postgres::Error> {
-        let query = format!(
-            "SELECT * FROM \"{}\" WHERE \"{}\" = $1",
-            T::table_name(),
-            T::id_name()
-        );
-        let mut rows = ergol.client().query(&query as &str, &[&self.id]).await?;
-        let row = rows.pop().unwrap();
-        Ok(<T as ToTable>::from_row(&row))
+    ) -> Result<T, Error> {
+        fetch(&self.id, true, ergol).await
     }
 }
 
-impl<T: ToTable, U: ToTable> Relation<U> for OneToOne<T> {
+impl<T: ToTable, U: ToTable> Relation<U> for OneToOne<T>
+where
+    T::Id: Key,
+{
     type Target = T;
     type Reverse = Option<U>;
 
-    fn from_rows(mut rows: Vec<tokio_postgres::Row>) -> Self::Reverse {
-        rows.pop().map(|x| <U as ToTable>::from_row(&x))
+    fn from_rows(mut rows: Vec<tokio_postgres::Row>) -> Result<Self::Reverse, Error> {
+        rows.pop().map(|x| <U as ToTable>::from_row(&x)).transpose()
     }
 }
 
-impl<T: ToTable> Pg for OneToOne<T> {
+impl<T: ToTable> Pg for OneToOne<T>
+where
+    T::Id: Key,
+{
     fn ty() -> String {
         format!(
-            "INT UNIQUE NOT NULL REFERENCES \"{}\" (\"{}\") ON DELETE CASCADE",
-            T::table_name(),
+            "{} UNIQUE REFERENCES {} (\"{}\")",
+            T::Id::ty(),
+            T::qualified_table_name(),
             T::id_name(),
         )
     }
 }
 
-impl<T: ToTable> From<T> for OneToOne<T> {
+impl<T: ToTable> From<T> for OneToOne<T>
+where
+    T::Id: Key,
+{
     fn from(t: T) -> OneToOne<T> {
         OneToOne::new(t.id())
     }
 }
 
-impl<T: ToTable> From<&T> for OneToOne<T> {
+impl<T: ToTable> From<&T> for OneToOne<T>
+where
+    T::Id: Key,
+{
     fn from(t: &T) -> OneToOne<T> {
         OneToOne::new(t.id())
     }
 }
 
-impl<'a, T: ToTable> FromSql<'a> for OneToOne<T> {
+impl<'a, T: ToTable> FromSql<'a> for OneToOne<T>
+where
+    T::Id: Key,
+{
     fn from_sql(
         ty: &Type,
         raw: &'a [u8],
     ) -> Result<Self, Box<dyn std::error::Error + 'static + Sync + Send>> {
-        Ok(OneToOne::new(i32::from_sql(ty, raw)?))
+        Ok(OneToOne::new(T::Id::from_sql(ty, raw)?))
     }
 
     fn accepts(ty: &Type) -> bool {
-        <i32 as FromSql>::accepts(ty)
+        <T::Id as FromSql>::accepts(ty)
     }
 }
 
-impl<T: ToTable> ToSql for OneToOne<T> {
+impl<T: ToTable> ToSql for OneToOne<T>
+where
+    T::Id: Key,
+{
     fn to_sql(
         &self,
         ty: &Type,
@@ -118,29 +178,37 @@ impl<T: ToTable> ToSql for OneToOne<T> {
     }
 
     fn accepts(ty: &Type) -> bool {
-        <i32 as ToSql>::accepts(ty)
+        <T::Id as ToSql>::accepts(ty)
     }
 
     to_sql_checked!();
 }
 
 #[cfg(feature = "with-serde")]
-impl<T: ToTable> Serialize for OneToOne<T> {
+impl<T: ToTable> Serialize for OneToOne<T>
+where
+    T::Id: Key,
+    T::Id: Serialize,
+{
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        serializer.serialize_i32(self.id)
+        self.id.serialize(serializer)
     }
 }
 
 #[cfg(feature = "with-serde")]
-impl<'de, T: ToTable> Deserialize<'de> for OneToOne<T> {
+impl<'de, T: ToTable> Deserialize<'de> for OneToOne<T>
+where
+    T::Id: Key,
+    T::Id: Deserialize<'de>,
+{
     fn deserialize<D>(deserializer: D) -> Result<OneToOne<T>, D::Error>
     where
         D: Deserializer<'de>,
     {
-        Ok(OneToOne::new(deserializer.deserialize_i32(I32Visitor)?))
+        Ok(OneToOne::new(T::Id::deserialize(deserializer)?))
     }
 }
 
@@ -148,16 +216,31 @@ impl<'de, T: ToTable> Deserialize<'de> for OneToOne<T> {
 ///
 /// You should not use this type by yourself, it should be automatically generated by the
 /// `#[many_to_one]` macro attribute.
-#[derive(Debug, Copy, Clone)]
-pub struct ManyToOne<T: ToTable> {
+#[derive(Debug)]
+pub struct ManyToOne<T: ToTable>
+where
+    T::Id: Key,
+{
     _phantom: PhantomData<T>,
     /// Creates a one to one relation ship.
-    id: i32,
+    id: T::Id,
+}
+
+impl<T: ToTable> Clone for ManyToOne<T>
+where
+    T::Id: Key,
+{
+    fn clone(&self) -> ManyToOne<T> {
+        ManyToOne::new(self.id.clone())
+    }
 }
 
-impl<T: ToTable> ManyToOne<T> {
+impl<T: ToTable> ManyToOne<T>
+where
+    T::Id: Key,
+{
     /// Creates a new many to one relationship.
-    pub fn new(id: i32) -> ManyToOne<T> {
+    pub fn new(id: T::Id) -> ManyToOne<T> {
         ManyToOne {
             _phantom: PhantomData,
             id,
@@ -165,55 +248,68 @@ impl<T: ToTable> ManyToOne<T> {
     }
 
     /// Fetches the element referenced by this relationship.
-    pub async fn fetch<Q: Queryable<impl GenericClient>>(
+    ///
+    /// Returns `Error::NotFound` if the element was soft deleted.
+    pub async fn fetch<Q: Queryable<impl GenericClient>>(&self, ergol: &Q) -> Result<T, Error> {
+        fetch(&self.id, false, ergol).await
+    }
+
+    /// Fetches the element referenced by this relationship, even if it was soft deleted.
+    pub async fn fetch_with_deleted<Q: Queryable<impl GenericClient>>(
         &self,
         ergol: &Q,
-    ) -> Result<T, tokio_postgres::Error> {
-        let query = format!(
-            "SELECT * FROM \"{}\" WHERE \"{}\" = $1",
-            T::table_name(),
-            T::id_name()
-        );
-        let mut rows = ergol.client().query(&query as &str, &[&self.id]).await?;
-        let row = rows.pop().unwrap();
-        Ok(<T as ToTable>::from_row(&row))
+    ) -> Result<T, Error> {
+        fetch(&self.id, true, ergol).await
     }
 }
 
-impl<T: ToTable, U: ToTable> Relation<U> for ManyToOne<T> {
+impl<T: ToTable, U: ToTable> Relation<U> for ManyToOne<T>
+where
+    T::Id: Key,
+{
     type Target = T;
     type Reverse = Vec<U>;
-    fn from_rows(rows: Vec<tokio_postgres::Row>) -> Self::Reverse {
+    fn from_rows(rows: Vec<tokio_postgres::Row>) -> Result<Self::Reverse, Error> {
         rows.into_iter()
             .map(|x| <U as ToTable>::from_row(&x))
             .collect()
     }
 }
 
-impl<T: ToTable> Pg for ManyToOne<T> {
+impl<T: ToTable> Pg for ManyToOne<T>
+where
+    T::Id: Key,
+{
     fn ty() -> String {
         format!(
-            "INT NOT NULL REFERENCES \"{}\" (\"{}\") ON DELETE CASCADE",
-            T::table_name(),
+            "{} REFERENCES {} (\"{}\")",
+            T::Id::ty(),
+            T::qualified_table_name(),
             T::id_name(),
         )
     }
 }
 
-impl<'a, T: ToTable> FromSql<'a> for ManyToOne<T> {
+impl<'a, T: ToTable> FromSql<'a> for ManyToOne<T>
+where
+    T::Id: Key,
+{
     fn from_sql(
         ty: &Type,
         raw: &'a [u8],
     ) -> Result<Self, Box<dyn std::error::Error + 'static + Sync + Send>> {
-        Ok(ManyToOne::new(i32::from_sql(ty, raw)?))
+        Ok(ManyToOne::new(T::Id::from_sql(ty, raw)?))
     }
 
     fn accepts(ty: &Type) -> bool {
-        <i32 as FromSql>::accepts(ty)
+        <T::Id as FromSql>::accepts(ty)
     }
 }
 
-impl<T: ToTable> ToSql for ManyToOne<T> {
+impl<T: ToTable> ToSql for ManyToOne<T>
+where
+    T::Id: Key,
+{
     fn to_sql(
         &self,
         ty: &Type,
@@ -223,59 +319,54 @@ impl<T: ToTable> ToSql for ManyToOne<T> {
     }
 
     fn accepts(ty: &Type) -> bool {
-        <i32 as ToSql>::accepts(ty)
+        <T::Id as ToSql>::accepts(ty)
     }
 
     to_sql_checked!();
 }
 
-impl<T: ToTable> From<T> for ManyToOne<T> {
+impl<T: ToTable> From<T> for ManyToOne<T>
+where
+    T::Id: Key,
+{
     fn from(t: T) -> ManyToOne<T> {
         ManyToOne::new(t.id())
     }
 }
 
-impl<T: ToTable> From<&T> for ManyToOne<T> {
+impl<T: ToTable> From<&T> for ManyToOne<T>
+where
+    T::Id: Key,
+{
     fn from(t: &T) -> ManyToOne<T> {
         ManyToOne::new(t.id())
     }
 }
 
 #[cfg(feature = "with-serde")]
-impl<T: ToTable> Serialize for ManyToOne<T> {
+impl<T: ToTable> Serialize for ManyToOne<T>
+where
+    T::Id: Key,
+    T::Id: Serialize,
+{
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        serializer.serialize_i32(self.id)
+        self.id.serialize(serializer)
     }
 }
 
 #[cfg(feature = "with-serde")]
-impl<'de, T: ToTable> Deserialize<'de> for ManyToOne<T> {
+impl<'de, T: ToTable> Deserialize<'de> for ManyToOne<T>
+where
+    T::Id: Key,
+    T::Id: Deserialize<'de>,
+{
     fn deserialize<D>(deserializer: D) -> Result<ManyToOne<T>, D::Error>
     where
         D: Deserializer<'de>,
     {
-        Ok(ManyToOne::new(deserializer.deserialize_i32(I32Visitor)?))
-    }
-}
-
-#[cfg(feature = "with-serde")]
-struct I32Visitor;
-
-#[cfg(feature = "with-serde")]
-impl<'de> Visitor<'de> for I32Visitor {
-    type Value = i32;
-
-    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-        formatter.write_str("an integer between -2^31 and 2^31")
-    }
-
-    fn visit_i32<E>(self, value: i32) -> Result<Self::Value, E>
-    where
-        E: de::Error,
-    {
-        Ok(value)
+        Ok(ManyToOne::new(T::Id::deserialize(deserializer)?))
     }
 }
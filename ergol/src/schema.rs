@@ -0,0 +1,217 @@
+//! This module contains the schema of the database, made of the descriptions of the structs and
+//! the enums of the program.
+//!
+//! The `#[ergol]` and `#[derive(PgEnum)]` macros register the json description of each element.
+//! The elements can be listed with [`all`], created with [`create_all`] and dropped with
+//! [`drop_all`].
+//!
+//! `ergol dump-schema` runs the program to write the descriptions in the `migrations/current`
+//! directory. The program needs to call [`dump_if_requested`] at the beginning of its main
+//! function:
+//!
+//! ```no_run
+//! # use ergol::prelude::*;
+//! #[ergol]
+//! pub struct User {
+//!     #[id] pub id: i32,
+//!     pub username: String,
+//! }
+//!
+//! fn main() {
+//!     ergol::schema::dump_if_requested();
+//!
+//!     // The rest of the program...
+//! }
+//! ```
+
+use std::env::var_os;
+use std::fs::{create_dir_all, read_dir, remove_file, write};
+use std::io;
+use std::path::Path;
+use std::process::exit;
+
+use tokio_postgres::GenericClient;
+
+use ergol_core::Element;
+
+use crate::{Error, Queryable};
+
+/// The environment variable that asks the program to dump its schema in a directory.
+pub const DUMP_SCHEMA: &str = "ERGOL_DUMP_SCHEMA";
+
+/// The json description of a struct or an enum, registered by the macros.
+#[derive(Debug)]
+pub struct Entry {
+    /// The name of the struct or the enum, that gives its name to the json file.
+    pub name: &'static str,
+
+    /// The json description of the elements of the struct or the enum.
+    pub json: &'static str,
+}
+
+inventory::collect!(Entry);
+
+/// Returns the entries registered by the macros, sorted by name.
+///
+/// ```
+/// # use ergol::prelude::*;
+/// #[ergol]
+/// pub struct User {
+///     #[id] pub id: i32,
+///     pub username: String,
+/// }
+///
+/// assert!(ergol::schema::entries().iter().any(|x| x.name == "User"));
+/// ```
+pub fn entries() -> Vec<&'static Entry> {
+    let mut entries = inventory::iter::<Entry>.into_iter().collect::<Vec<_>>();
+    entries.sort_by_key(|x| x.name);
+    entries
+}
+
+/// Returns the elements of the schema, in the order in which they can be created: the schemas,
+/// the enums, and the tables sorted so that each table comes after the tables it references.
+///
+/// The references between the structs are resolved with the schemas of their tables, even when
+/// tables of different schemas have the same name:
+///
+/// ```
+/// # use ergol::prelude::*;
+/// # use ergol::ergol_core::Element;
+/// #[ergol(table = "accounts", schema = "auth")]
+/// pub struct Owner {
+///     #[id] pub id: i32,
+///     pub email: String,
+/// }
+///
+/// #[ergol(table = "accounts", schema = "billing")]
+/// pub struct Ledger {
+///     #[id] pub id: i32,
+///     #[many_to_one(ledgers)] pub owner: Owner,
+/// }
+///
+/// let owner = ergol::schema::all()
+///     .into_iter()
+///     .find_map(|x| match x {
+///         Element::Table(t) if t.schema.as_deref() == Some("billing") => t
+///             .columns
+///             .into_iter()
+///             .find(|x| x.name == "owner"),
+///         _ => None,
+///     })
+///     .unwrap();
+///
+/// let key = owner.ty.foreign_key().unwrap();
+/// assert_eq!(key.table, "accounts");
+/// assert_eq!(key.schema.as_deref(), Some("auth"));
+/// ```
+pub fn all() -> Vec<Element> {
+    let entities = entries()
+        .into_iter()
+        .map(|x| {
+            let elements = serde_json::from_str(x.json).expect("the macros generate valid json");
+            (x.name.to_owned(), elements)
+        })
+        .collect();
+
+    ergol_core::elements(ergol_core::state(entities))
+}
+
+/// Creates all the elements of the schema.
+///
+/// ```
+/// # use ergol::prelude::*;
+/// # use ergol::tokio;
+/// #[ergol]
+/// pub struct Author {
+///     #[id] pub id: i32,
+///     pub name: String,
+/// }
+///
+/// #[ergol]
+/// pub struct Book {
+///     #[id] pub id: i32,
+///     pub title: String,
+///     #[many_to_one(books)] pub author: Author,
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), ergol::Error> {
+/// #     let (client, connection) = ergol::connect(
+/// #         "host=localhost user=ergol password=ergol dbname=ergol",
+/// #         ergol::tokio_postgres::NoTls,
+/// #     )
+/// #     .await?;
+/// #     tokio::spawn(async move {
+/// #         if let Err(e) = connection.await {
+/// #             eprintln!("connection error: {}", e);
+/// #         }
+/// #     });
+/// // The books table is dropped before the authors table, and created after it.
+/// ergol::drop_all(&client).await?;
+/// ergol::create_all(&client).await?;
+///
+/// let author = Author::create("Victor Hugo").save(&client).await?;
+/// Book::create("Les Misérables", &author).save(&client).await?;
+/// assert_eq!(author.books(&client).await?.len(), 1);
+/// # Ok(())
+/// # }
+/// ```
+pub async fn create_all<Q: Queryable<impl GenericClient>>(db: &Q) -> Result<(), Error> {
+    let query = all()
+        .iter()
+        .map(Element::create)
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Ok(db.client().batch_execute(&query as &str).await?)
+}
+
+/// Drops the elements of the schema that exist, in the reverse order of their creation.
+///
+/// The schemas are dropped with everything they contain.
+pub async fn drop_all<Q: Queryable<impl GenericClient>>(db: &Q) -> Result<(), Error> {
+    let query = all()
+        .iter()
+        .rev()
+        .map(Element::drop_if_exists)
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Ok(db.client().batch_execute(&query as &str).await?)
+}
+
+/// Writes the json file of each entry in a directory.
+///
+/// The json files of the directory are removed first, so that the elements that no longer exist
+/// are removed from the schema.
+pub fn dump<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    let path = path.as_ref();
+    create_dir_all(path)?;
+
+    for file in read_dir(path)? {
+        let file = file?.path();
+        if file.extension().and_then(|x| x.to_str()) == Some("json") {
+            remove_file(file)?;
+        }
+    }
+
+    for entry in entries() {
+        write(path.join(format!("{}.json", entry.name)), entry.json)?;
+    }
+
+    Ok(())
+}
+
+/// Dumps the schema in the directory given by the `ERGOL_DUMP_SCHEMA` environment variable and
+/// exits, if the variable is set.
+pub fn dump_if_requested() {
+    if let Some(path) = var_os(DUMP_SCHEMA) {
+        if let Err(e) = dump(&path) {
+            eprintln!("couldn't dump the schema: {}", e);
+            exit(1);
+        }
+
+        exit(0);
+    }
+}
@@ -0,0 +1,70 @@
+use ergol::prelude::*;
+use ergol::tokio;
+use ergol::tokio_postgres::NoTls;
+use ergol::Error;
+
+#[rustfmt::skip]
+#[ergol]
+pub struct User {
+    #[id] pub id: i32,
+    #[unique] pub username: String,
+}
+
+#[rustfmt::skip]
+#[ergol(unique(owner, name))]
+pub struct Project {
+    #[id] pub id: i32,
+    pub name: String,
+    #[check("stars >= 0")] pub stars: i32,
+    #[many_to_one(projects, on_delete = "restrict")] pub owner: User,
+}
+
+#[rustfmt::skip]
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    ergol::schema::dump_if_requested();
+
+    let (client, connection) = ergol::connect(
+        "host=localhost user=ergol password=ergol",
+        NoTls,
+    )
+    .await?;
+
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            eprintln!("connection error: {}", e);
+        }
+    });
+
+    // Try to delete the database
+    Project::drop_table().execute(&client).await.ok();
+    User::drop_table().execute(&client).await.ok();
+
+    // Create the tables
+    User::create_table().execute(&client).await?;
+    Project::create_table().execute(&client).await?;
+
+    let thomas = User::create("thomas").save(&client).await?;
+    let nicolas = User::create("nicolas").save(&client).await?;
+
+    Project::create("ergol", 10, &thomas).save(&client).await?;
+    Project::create("ergol", 3, &nicolas).save(&client).await?;
+
+    // The owner and the name of a project are unique together
+    assert!(Project::create("ergol", 0, &thomas).save(&client).await.is_err());
+
+    // The stars can not be negative
+    assert!(Project::create("rocket", -1, &thomas).save(&client).await.is_err());
+
+    let project = Project::get_by_owner_and_name(&nicolas, "ergol", &client).await?.unwrap();
+    println!("{} has {} stars", project.name, project.stars);
+    assert_eq!(project.stars, 3);
+
+    // Users can't be deleted while they own projects
+    let copy = User::get_by_username("nicolas", &client).await?.unwrap();
+    assert!(copy.delete(&client).await.is_err());
+    project.delete(&client).await?;
+    nicolas.delete(&client).await?;
+
+    Ok(())
+}
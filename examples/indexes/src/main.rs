@@ -0,0 +1,55 @@
+use ergol::prelude::*;
+use ergol::tokio;
+use ergol::tokio_postgres::NoTls;
+use ergol::Error;
+
+#[rustfmt::skip]
+#[ergol(index(author, published, where = "published IS NOT NULL"))]
+pub struct Post {
+    #[id] pub id: i32,
+    #[index(unique)] pub slug: String,
+    #[index(using = "hash")] pub author: String,
+    pub published: Option<i32>,
+}
+
+#[rustfmt::skip]
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    ergol::schema::dump_if_requested();
+
+    let (client, connection) = ergol::connect(
+        "host=localhost user=ergol password=ergol",
+        NoTls,
+    )
+    .await?;
+
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            eprintln!("connection error: {}", e);
+        }
+    });
+
+    // Try to delete the database
+    Post::drop_table().execute(&client).await.ok();
+
+    // The indexes are created with the table
+    Post::create_table().execute(&client).await?;
+
+    let rows = client
+        .client
+        .query("SELECT indexdef FROM pg_indexes WHERE tablename = 'posts' ORDER BY indexname;", &[])
+        .await?;
+
+    for row in &rows {
+        println!("{}", row.get::<_, String>(0));
+    }
+
+    assert_eq!(rows.len(), 4);
+
+    Post::create("hello", "tforgione", Some(2021)).save(&client).await?;
+
+    // The unique index prevents duplicated slugs
+    assert!(Post::create("hello", "graydon", None).save(&client).await.is_err());
+
+    Ok(())
+}
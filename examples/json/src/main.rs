@@ -0,0 +1,109 @@
+use ergol::prelude::*;
+use ergol::tokio;
+use ergol::tokio_postgres::types::Json;
+use ergol::tokio_postgres::NoTls;
+use ergol::Error;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Settings {
+    pub theme: String,
+    pub language: String,
+    pub font_size: i32,
+}
+
+#[rustfmt::skip]
+#[ergol]
+pub struct Account {
+    #[id] pub id: i32,
+    #[unique] pub name: String,
+    #[index(using = "gin", ops = "jsonb_path_ops")] pub settings: Json<Settings>,
+    #[index(using = "gin")] pub extra: serde_json::Value,
+}
+
+/// Returns the sorted names of the accounts.
+fn names(accounts: Vec<Account>) -> Vec<String> {
+    let mut names = accounts.into_iter().map(|x| x.name).collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+#[rustfmt::skip]
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    ergol::schema::dump_if_requested();
+
+    let (client, connection) = ergol::connect(
+        "host=localhost user=ergol password=ergol",
+        NoTls,
+    )
+    .await?;
+
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            eprintln!("connection error: {}", e);
+        }
+    });
+
+    // Try to delete the database
+    Account::drop_table().execute(&client).await.ok();
+
+    // Create the tables
+    Account::create_table().execute(&client).await?;
+
+    let settings = |theme: &str, language: &str, font_size| Json(Settings {
+        theme: theme.to_owned(),
+        language: language.to_owned(),
+        font_size,
+    });
+
+    Account::create("alice", settings("dark", "en", 12), json!({ "beta": true })).save(&client).await?;
+    Account::create("bob", settings("light", "fr", 14), json!({})).save(&client).await?;
+    Account::create("carol", settings("dark", "fr", 16), json!({ "beta": false, "admin": true })).save(&client).await?;
+
+    // The values that contain another json value
+    let dark = Account::select()
+        .filter(account::settings::contains(json!({ "theme": "dark" })))
+        .execute(&client)
+        .await?;
+    assert_eq!(names(dark), vec!["alice", "carol"]);
+
+    // The values that have a key
+    let beta = Account::select()
+        .filter(account::extra::has_key("beta"))
+        .execute(&client)
+        .await?;
+    assert_eq!(names(beta), vec!["alice", "carol"]);
+
+    // A field compared as text
+    let french = Account::select()
+        .filter(account::settings::field_text("language").eq("fr"))
+        .execute(&client)
+        .await?;
+    assert_eq!(names(french), vec!["bob", "carol"]);
+
+    // A field compared as json
+    let large = Account::select()
+        .filter(account::settings::field("font_size").geq(json!(14)))
+        .execute(&client)
+        .await?;
+    assert_eq!(names(large), vec!["bob", "carol"]);
+
+    // The filters can be combined
+    let accounts = Account::select()
+        .filter(
+            account::settings::contains(json!({ "theme": "dark" }))
+                .and(account::extra::field("admin").eq(json!(true))),
+        )
+        .execute(&client)
+        .await?;
+    assert_eq!(names(accounts), vec!["carol"]);
+
+    for account in Account::select().execute(&client).await? {
+        println!("{}: {:?} {}", account.name, account.settings.0, account.extra);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,112 @@
+use ergol::prelude::*;
+use ergol::tokio;
+use ergol::tokio_postgres::NoTls;
+use ergol::Error;
+
+use uuid::Uuid;
+
+#[rustfmt::skip]
+#[ergol]
+pub struct Country {
+    #[id] pub code: String,
+    pub name: String,
+}
+
+#[rustfmt::skip]
+#[ergol]
+pub struct City {
+    #[id] pub id: i64,
+    pub name: String,
+    #[many_to_one(cities)] pub country: Country,
+}
+
+#[rustfmt::skip]
+#[ergol]
+pub struct Traveler {
+    #[id] pub id: Uuid,
+    #[unique] pub name: String,
+    #[many_to_many(travelers)] pub destinations: City,
+}
+
+#[rustfmt::skip]
+#[ergol]
+pub struct Visa {
+    #[id] pub country: String,
+    #[id] pub number: i32,
+    pub holder: String,
+}
+
+#[rustfmt::skip]
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    ergol::schema::dump_if_requested();
+
+    let (client, connection) = ergol::connect(
+        "host=localhost user=ergol password=ergol",
+        NoTls,
+    )
+    .await?;
+
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            eprintln!("connection error: {}", e);
+        }
+    });
+
+    // Try to delete the database
+    Visa::drop_table().execute(&client).await.ok();
+    Traveler::drop_table().execute(&client).await.ok();
+    City::drop_table().execute(&client).await.ok();
+    Country::drop_table().execute(&client).await.ok();
+
+    // Create the tables
+    Country::create_table().execute(&client).await?;
+    City::create_table().execute(&client).await?;
+    Traveler::create_table().execute(&client).await?;
+    Visa::create_table().execute(&client).await?;
+
+    // Natural keys are given when creating the element
+    let france = Country::create("fr", "France").save(&client).await?;
+    let italy = Country::create("it", "Italy").save(&client).await?;
+
+    let paris = City::create("Paris", &france).save(&client).await?;
+    let rome = City::create("Rome", &italy).save(&client).await?;
+
+    // Uuids are generated by the database
+    let traveler = Traveler::create("tforgione").save(&client).await?;
+    println!("{} has id {}", traveler.name, traveler.id);
+
+    traveler.add_destination(&paris, &client).await?;
+    traveler.add_destination(&rome, &client).await?;
+
+    for city in traveler.destinations(&client).await? {
+        let country = city.country(&client).await?;
+        println!("{} has visited {} ({})", traveler.name, city.name, country.code);
+    }
+
+    let cities = france.cities(&client).await?;
+    assert_eq!(cities.len(), 1);
+    assert_eq!(cities[0].id, paris.id);
+
+    let traveler = Traveler::get_by_id(traveler.id, &client).await?.unwrap();
+    assert_eq!(paris.travelers(&client).await?[0].id, traveler.id);
+
+    // Composite keys are made of several columns
+    Visa::create("fr", 1, "tforgione").save(&client).await?;
+    Visa::create("it", 1, "tforgione").save(&client).await?;
+
+    let mut visa = Visa::get_by_country_and_number("fr", 1, &client).await?.unwrap();
+    assert_eq!(visa.id(), (String::from("fr"), 1));
+
+    visa.holder = String::from("graydon");
+    visa.save(&client).await?;
+
+    let visa = Visa::get_by_country_and_number("fr", 1, &client).await?.unwrap();
+    println!("Visa {:?} belongs to {}", visa.id(), visa.holder);
+    assert_eq!(Visa::get_by_country_and_number("it", 1, &client).await?.unwrap().holder, "tforgione");
+
+    visa.delete(&client).await?;
+    assert_eq!(Visa::select().execute(&client).await?.len(), 1);
+
+    Ok(())
+}
@@ -1,7 +1,8 @@
 use ergol::prelude::*;
 use ergol::tokio;
 use ergol::tokio_postgres::types::Json;
-use ergol::tokio_postgres::{Error, NoTls};
+use ergol::tokio_postgres::NoTls;
+use ergol::Error;
 
 use serde::{Deserialize, Serialize};
 
@@ -46,6 +47,8 @@ pub struct Project {
 #[rustfmt::skip]
 #[tokio::main]
 async fn main() -> Result<(), Error> {
+    ergol::schema::dump_if_requested();
+
     let (client, connection) = ergol::connect(
         "host=localhost user=ergol password=ergol",
         NoTls,
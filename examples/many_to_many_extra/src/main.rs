@@ -1,6 +1,7 @@
 use ergol::prelude::*;
 use ergol::tokio;
-use ergol::tokio_postgres::{Error, NoTls};
+use ergol::tokio_postgres::NoTls;
+use ergol::Error;
 
 #[rustfmt::skip]
 #[ergol]
@@ -27,6 +28,8 @@ pub struct Project {
 #[rustfmt::skip]
 #[tokio::main]
 async fn main() -> Result<(), Error> {
+    ergol::schema::dump_if_requested();
+
     let (client, connection) = ergol::connect(
         "host=localhost user=ergol password=ergol",
         NoTls,
@@ -39,15 +42,9 @@ async fn main() -> Result<(), Error> {
         }
     });
 
-    // Try to delete the database
-    User::drop_table().execute(&client).await.ok();
-    Project::drop_table().execute(&client).await.ok();
-    Role::drop_type().execute(&client).await.ok();
-
-    // Create the tables
-    Role::create_type().execute(&client).await?;
-    User::create_table().execute(&client).await?;
-    Project::create_table().execute(&client).await?;
+    // Delete the database, and create the type and the tables in the right order
+    ergol::drop_all(&client).await?;
+    ergol::create_all(&client).await?;
 
     // Create users
     User::create("graydon").save(&client).await?;
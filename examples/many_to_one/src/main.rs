@@ -1,7 +1,8 @@
 use ergol::prelude::*;
 use ergol::tokio;
 use ergol::tokio_postgres::types::Json;
-use ergol::tokio_postgres::{Error, NoTls};
+use ergol::tokio_postgres::NoTls;
+use ergol::Error;
 
 use serde::{Deserialize, Serialize};
 
@@ -44,11 +45,14 @@ pub struct Project {
     #[id] pub id: i32,
     pub name: String,
     #[many_to_one(projects)] pub owner: User,
+    #[many_to_one(reviewed, on_delete = "set_null")] pub reviewer: Option<User>,
 }
 
 #[rustfmt::skip]
 #[tokio::main]
 async fn main() -> Result<(), Error> {
+    ergol::schema::dump_if_requested();
+
     let (client, connection) = ergol::connect(
         "host=localhost user=ergol password=ergol",
         NoTls,
@@ -79,10 +83,11 @@ async fn main() -> Result<(), Error> {
 
     let tforgione = User::get_by_username("tforgione", &client).await?.unwrap();
     let graydon = User::get_by_username("graydon", &client).await?.unwrap();
+    let evan = User::get_by_username("evan", &client).await?.unwrap();
 
-    Project::create("My first project", &tforgione).save(&client).await?;
-    Project::create("My second project", &tforgione).save(&client).await?;
-    Project::create("My third project", &tforgione).save(&client).await?;
+    Project::create("My first project", &tforgione, Some(&evan)).save(&client).await?;
+    Project::create("My second project", &tforgione, Some(&evan)).save(&client).await?;
+    Project::create("My third project", &tforgione, None).save(&client).await?;
 
     // Select all users
     let mut users = User::select().execute(&client).await?;
@@ -128,5 +133,22 @@ async fn main() -> Result<(), Error> {
         println!("  - {}", project.name);
     }
 
+    // Optional relations can be empty
+    println!("\nReviewers");
+    for project in Project::select().execute(&client).await? {
+        match project.reviewer(&client).await? {
+            Some(reviewer) => println!("Project \"{}\" reviewed by \"{}\"", project.name, reviewer.username),
+            None => println!("Project \"{}\" not reviewed", project.name),
+        }
+    }
+
+    assert_eq!(evan.reviewed(&client).await?.len(), 2);
+
+    // The reviewer of a project is removed when the reviewer is deleted
+    evan.delete(&client).await?;
+    for project in Project::select().execute(&client).await? {
+        assert!(project.reviewer(&client).await?.is_none());
+    }
+
     Ok(())
 }
@@ -0,0 +1,90 @@
+use ergol::prelude::*;
+use ergol::tokio;
+use ergol::tokio_postgres::NoTls;
+use ergol::Error;
+
+#[rustfmt::skip]
+#[ergol(table = "categories")]
+pub struct Category {
+    #[id] pub id: i32,
+    #[unique] #[column(name = "label")] pub name: String,
+}
+
+#[rustfmt::skip]
+#[ergol(table = "inventory")]
+pub struct Product {
+    #[id] #[column(name = "product_id")] pub id: i32,
+    #[column(name = "product_name")] pub name: String,
+    #[many_to_one(products)] #[column(name = "category_id")] pub category: Category,
+    #[many_to_many(products)] pub tags: Tag,
+}
+
+#[rustfmt::skip]
+#[ergol(table = "labels")]
+pub struct Tag {
+    #[id] #[column(name = "tag_id")] pub id: i32,
+    pub name: String,
+}
+
+#[rustfmt::skip]
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    ergol::schema::dump_if_requested();
+
+    let (client, connection) = ergol::connect(
+        "host=localhost user=ergol password=ergol",
+        NoTls,
+    )
+    .await?;
+
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            eprintln!("connection error: {}", e);
+        }
+    });
+
+    // Try to delete the database
+    Product::drop_table().execute(&client).await.ok();
+    Tag::drop_table().execute(&client).await.ok();
+    Category::drop_table().execute(&client).await.ok();
+
+    // Create the tables
+    Category::create_table().execute(&client).await?;
+    Tag::create_table().execute(&client).await?;
+    Product::create_table().execute(&client).await?;
+
+    let fruits = Category::create("fruits").save(&client).await?;
+    let mut apple = Product::create("apple", &fruits).save(&client).await?;
+    let banana = Product::create("banana", &fruits).save(&client).await?;
+
+    let yellow = Tag::create("yellow").save(&client).await?;
+    banana.add_tag(&yellow, &client).await?;
+
+    apple.name = String::from("green apple");
+    apple.save(&client).await?;
+
+    // The queries use the names of the tables and columns
+    let fruits = Category::get_by_name("fruits", &client).await?.unwrap();
+    for product in fruits.products(&client).await? {
+        println!("{} is in {}", product.name, product.category(&client).await?.name);
+    }
+
+    let products = Product::select()
+        .filter(product::name::like("%apple"))
+        .execute(&client)
+        .await?;
+    assert_eq!(products.len(), 1);
+
+    for product in yellow.products(&client).await? {
+        println!("{} is {}", product.name, yellow.name);
+    }
+    assert_eq!(banana.tags(&client).await?.len(), 1);
+
+    apple.delete(&client).await?;
+    assert_eq!(fruits.products(&client).await?.len(), 1);
+
+    let rows = client.client().query("SELECT \"product_name\" FROM \"inventory\";", &[]).await?;
+    assert_eq!(rows[0].get::<_, String>(0), "banana");
+
+    Ok(())
+}
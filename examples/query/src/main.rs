@@ -1,6 +1,7 @@
 use ergol::prelude::*;
 use ergol::tokio;
-use ergol::tokio_postgres::{Error, NoTls};
+use ergol::tokio_postgres::NoTls;
+use ergol::Error;
 
 #[rustfmt::skip]
 #[ergol]
@@ -22,6 +23,8 @@ pub struct Project {
 #[rustfmt::skip]
 #[tokio::main]
 async fn main() -> Result<(), Error> {
+    ergol::schema::dump_if_requested();
+
     let (client, connection) = ergol::connect(
         "host=localhost user=ergol password=ergol",
         NoTls,
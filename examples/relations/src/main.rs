@@ -0,0 +1,75 @@
+use ergol::prelude::*;
+use ergol::tokio;
+use ergol::tokio_postgres::NoTls;
+use ergol::Error;
+
+#[rustfmt::skip]
+#[ergol]
+pub struct Category {
+    #[id] pub id: i32,
+    pub name: String,
+    #[many_to_one(children)] pub parent: Option<Category>,
+}
+
+#[rustfmt::skip]
+#[ergol]
+pub struct User {
+    #[id] pub id: i32,
+    #[unique] pub username: String,
+    #[many_to_many(followers)] pub followings: User,
+}
+
+#[rustfmt::skip]
+#[ergol]
+pub struct Article {
+    #[id] pub id: i32,
+    pub title: String,
+    #[many_to_one(authored)] pub author: User,
+    #[many_to_one(reviewed)] pub reviewer: Option<User>,
+}
+
+#[rustfmt::skip]
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    ergol::schema::dump_if_requested();
+
+    let (client, connection) = ergol::connect(
+        "host=localhost user=ergol password=ergol",
+        NoTls,
+    )
+    .await?;
+
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            eprintln!("connection error: {}", e);
+        }
+    });
+
+    ergol::drop_all(&client).await?;
+    ergol::create_all(&client).await?;
+
+    // A struct can reference itself
+    let rust = Category::create("rust", None).save(&client).await?;
+    let ergol = Category::create("ergol", Some(&rust)).save(&client).await?;
+    assert_eq!(ergol.parent(&client).await?.unwrap().name, "rust");
+    assert_eq!(rust.children(&client).await?[0].name, "ergol");
+
+    // A struct can have a many to many relation with itself
+    let thomas = User::create("thomas").save(&client).await?;
+    let nicolas = User::create("nicolas").save(&client).await?;
+    thomas.add_following(&nicolas, &client).await?;
+
+    for follower in nicolas.followers(&client).await? {
+        println!("{} follows {}", follower.username, nicolas.username);
+    }
+    assert_eq!(thomas.followings(&client).await?[0].username, "nicolas");
+    assert!(thomas.followers(&client).await?.is_empty());
+
+    // Relations to the same struct are distinguished by their names
+    Article::create("Self references", &thomas, Some(&nicolas)).save(&client).await?;
+    assert_eq!(thomas.authored(&client).await?.len(), 1);
+    assert!(thomas.reviewed(&client).await?.is_empty());
+    assert_eq!(nicolas.reviewed(&client).await?[0].title, "Self references");
+
+    Ok(())
+}
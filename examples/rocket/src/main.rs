@@ -76,6 +76,8 @@ async fn list_items(db: Db) -> String {
 
 #[rocket::main]
 async fn main() -> Result<(), rocket::Error> {
+    ergol::schema::dump_if_requested();
+
     // Setup rocket with its database connections pool.
     let rocket = rocket::build()
         .attach(AdHoc::on_ignite("Database", |rocket| async move {
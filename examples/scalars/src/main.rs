@@ -0,0 +1,128 @@
+use std::convert::TryFrom;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use ergol::ergol_core::{Element, Ty};
+use ergol::pg::Interval;
+use ergol::prelude::*;
+use ergol::tokio;
+use ergol::tokio_postgres::NoTls;
+use ergol::Error;
+
+use rust_decimal::Decimal;
+use serde_json::json;
+
+// An enum of the program whose name is also the name of a type of serde_json
+#[derive(PgEnum, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Value {
+    Low,
+    High,
+}
+
+#[rustfmt::skip]
+#[ergol]
+pub struct Measure {
+    #[id] pub id: i32,
+    pub grade: i8,
+    pub count: i16,
+    pub oid: u32,
+    pub payload: Vec<u8>,
+    pub address: IpAddr,
+    pub duration: Interval,
+    pub data: serde_json::Value,
+    pub price: Decimal,
+    pub level: Value,
+    pub margin: Option<rust_decimal::Decimal>,
+}
+
+#[rustfmt::skip]
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    ergol::schema::dump_if_requested();
+
+    let (client, connection) = ergol::connect(
+        "host=localhost user=ergol password=ergol",
+        NoTls,
+    )
+    .await?;
+
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            eprintln!("connection error: {}", e);
+        }
+    });
+
+    // Try to delete the database
+    Measure::drop_table().execute(&client).await.ok();
+    Value::drop_type().execute(&client).await.ok();
+
+    // Create the tables
+    Value::create_type().execute(&client).await?;
+    Measure::create_table().execute(&client).await?;
+
+    let address: IpAddr = "192.168.0.1".parse().unwrap();
+    let duration = Interval::try_from(Duration::from_secs(90)).unwrap();
+    let measure = Measure::create(
+        -3i8,
+        1024i16,
+        42u32,
+        vec![0xde, 0xad, 0xbe, 0xef],
+        address,
+        duration,
+        json!({ "unit": "cm", "values": [1, 2, 3] }),
+        Decimal::new(1234, 2),
+        Value::High,
+        None,
+    )
+    .save(&client)
+    .await?;
+
+    // Every value is read as it was written
+    let read = Measure::get_by_id(measure.id, &client).await?.unwrap();
+    assert_eq!(read.grade, -3);
+    assert_eq!(read.count, 1024);
+    assert_eq!(read.oid, 42);
+    assert_eq!(read.payload, vec![0xde, 0xad, 0xbe, 0xef]);
+    assert_eq!(read.address, address);
+    assert_eq!(read.duration.to_duration(), Some(Duration::from_secs(90)));
+    assert_eq!(read.data["values"][2], 3);
+    assert_eq!(read.price, Decimal::new(1234, 2));
+    assert_eq!(read.level, Value::High);
+    assert_eq!(read.margin, None);
+
+    // The enum of the program has its own type, the json column is a JSONB column
+    let rows = client
+        .client()
+        .query(
+            "SELECT column_name, udt_name FROM information_schema.columns WHERE table_name = 'measures' AND column_name IN ('data', 'level') ORDER BY column_name;",
+            &[],
+        )
+        .await?;
+    let types = rows.iter().map(|x| x.get::<_, String>(1)).collect::<Vec<_>>();
+    assert_eq!(types, vec!["jsonb", "value"]);
+
+    // The migrations know the types, whether they are written with their paths or imported
+    let columns = ergol::schema::all()
+        .into_iter()
+        .find_map(|x| match x {
+            Element::Table(t) if t.name == "measures" => Some(t.columns),
+            _ => None,
+        })
+        .unwrap();
+    let types = columns.into_iter().map(|x| x.ty).skip(5).collect::<Vec<_>>();
+    assert_eq!(
+        types,
+        vec![
+            Ty::IpAddr,
+            Ty::Interval,
+            Ty::Jsonb,
+            Ty::Decimal,
+            Ty::Enum(String::from("value")),
+            Ty::Option(Box::new(Ty::Decimal)),
+        ]
+    );
+
+    println!("{:?}", read);
+
+    Ok(())
+}
@@ -0,0 +1,83 @@
+use ergol::prelude::*;
+use ergol::tokio;
+use ergol::tokio_postgres::NoTls;
+use ergol::Error;
+
+#[rustfmt::skip]
+#[ergol]
+pub struct User {
+    #[id] pub id: i32,
+    #[unique] pub username: String,
+}
+
+#[rustfmt::skip]
+#[ergol(schema = "billing")]
+pub struct Invoice {
+    #[id] pub id: i32,
+    pub amount: i32,
+    #[many_to_one(invoices)] pub customer: User,
+    #[many_to_many(invoices)] pub products: Product,
+}
+
+#[rustfmt::skip]
+#[ergol(schema = "billing")]
+pub struct Product {
+    #[id] pub id: i32,
+    #[unique] pub name: String,
+}
+
+#[rustfmt::skip]
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    ergol::schema::dump_if_requested();
+
+    let (client, connection) = ergol::connect(
+        "host=localhost user=ergol password=ergol",
+        NoTls,
+    )
+    .await?;
+
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            eprintln!("connection error: {}", e);
+        }
+    });
+
+    // Try to delete the database
+    Invoice::drop_table().execute(&client).await.ok();
+    Product::drop_table().execute(&client).await.ok();
+    User::drop_table().execute(&client).await.ok();
+
+    // Create the tables, the billing schema is created with them
+    User::create_table().execute(&client).await?;
+    Product::create_table().execute(&client).await?;
+    Invoice::create_table().execute(&client).await?;
+
+    let tforgione = User::create("tforgione").save(&client).await?;
+    let book = Product::create("book").save(&client).await?;
+
+    let invoice = Invoice::create(12, &tforgione).save(&client).await?;
+    invoice.add_product(&book, &client).await?;
+
+    for invoice in tforgione.invoices(&client).await? {
+        println!("{} owes {}", invoice.customer(&client).await?.username, invoice.amount);
+        for product in invoice.products(&client).await? {
+            println!("  - {}", product.name);
+        }
+    }
+
+    assert_eq!(book.invoices(&client).await?.len(), 1);
+    assert!(Product::get_by_name("book", &client).await?.is_some());
+
+    // The tables live in the billing schema
+    let rows = client
+        .client()
+        .query("SELECT * FROM pg_tables WHERE schemaname = 'billing';", &[])
+        .await?;
+    assert_eq!(rows.len(), 3);
+
+    invoice.delete(&client).await?;
+    assert!(Invoice::select().execute(&client).await?.is_empty());
+
+    Ok(())
+}
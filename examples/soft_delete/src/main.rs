@@ -0,0 +1,87 @@
+use ergol::prelude::*;
+use ergol::tokio;
+use ergol::tokio_postgres::NoTls;
+use ergol::Error;
+
+use chrono::{DateTime, Utc};
+
+#[rustfmt::skip]
+#[ergol]
+pub struct User {
+    #[id] pub id: i32,
+    #[unique] pub username: String,
+    #[soft_delete] pub deleted_at: Option<DateTime<Utc>>,
+}
+
+#[rustfmt::skip]
+#[ergol]
+pub struct Project {
+    #[id] pub id: i32,
+    pub name: String,
+    #[many_to_one(projects)] pub owner: User,
+    #[soft_delete] pub deleted_at: Option<DateTime<Utc>>,
+}
+
+#[rustfmt::skip]
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    ergol::schema::dump_if_requested();
+
+    let (client, connection) = ergol::connect(
+        "host=localhost user=ergol password=ergol",
+        NoTls,
+    )
+    .await?;
+
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            eprintln!("connection error: {}", e);
+        }
+    });
+
+    // Try to delete the database
+    Project::drop_table().execute(&client).await.ok();
+    User::drop_table().execute(&client).await.ok();
+
+    // Create the tables
+    User::create_table().execute(&client).await?;
+    Project::create_table().execute(&client).await?;
+
+    // The soft delete column is not part of the create function
+    let graydon = User::create("graydon").save(&client).await?;
+    let tforgione = User::create("tforgione").save(&client).await?;
+
+    Project::create("My first project", &tforgione).save(&client).await?;
+    let project = Project::create("My second project", &tforgione).save(&client).await?;
+    let compiler = Project::create("A compiler", &graydon).save(&client).await?;
+
+    // Deleting a user only marks it as deleted
+    graydon.delete(&client).await?;
+    assert!(User::get_by_username("graydon", &client).await?.is_none());
+    assert_eq!(User::select().execute(&client).await?.len(), 1);
+    assert_eq!(User::select().with_deleted().execute(&client).await?.len(), 2);
+
+    // The deleted owner is not fetched through the relation, unless it is asked for
+    assert!(matches!(compiler.owner(&client).await, Err(Error::NotFound)));
+    assert_eq!(compiler.owner.fetch_with_deleted(&client).await?.username, "graydon");
+
+    // Deleted projects are excluded from the relations
+    project.delete(&client).await?;
+    let projects = tforgione.projects(&client).await?;
+    println!("{}'s projects ({} projects):", tforgione.username, projects.len());
+    for project in &projects {
+        println!("  - {}", project.name);
+    }
+    assert_eq!(projects.len(), 1);
+
+    // Deleted elements can be restored
+    for mut user in User::select().only_deleted().execute(&client).await? {
+        println!("Restoring {}", user.username);
+        user.restore(&client).await?;
+    }
+
+    assert!(User::get_by_username("graydon", &client).await?.is_some());
+    assert!(User::select().only_deleted().execute(&client).await?.is_empty());
+
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use ergol::prelude::*;
+use ergol::tokio;
+use ergol::tokio_postgres::NoTls;
+use ergol::Error;
+
+#[rustfmt::skip]
+#[ergol]
+pub struct Note {
+    #[id] pub id: i32,
+    pub body: String,
+}
+
+#[rustfmt::skip]
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    ergol::schema::dump_if_requested();
+
+    let (mut client, connection) = ergol::connect(
+        "host=localhost user=ergol password=ergol",
+        NoTls,
+    )
+    .await?;
+
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            eprintln!("connection error: {}", e);
+        }
+    });
+
+    // Each tenant has its own schema
+    for tenant in ["tenant_a", "tenant_b"] {
+        let query = format!("DROP SCHEMA IF EXISTS {0} CASCADE; CREATE SCHEMA {0};", tenant);
+        client.client.batch_execute(&query).await?;
+
+        let db = client.scoped(tenant).await?;
+        Note::create_table().execute(&db).await?;
+        db.release().await?;
+    }
+
+    // The queries of a scope only see the tables of its tenant
+    let a = client.scoped("tenant_a").await?;
+    Note::create("first note of a").save(&a).await?;
+    Note::create("second note of a").save(&a).await?;
+    assert_eq!(Note::select().execute(&a).await?.len(), 2);
+    a.release().await?;
+
+    // In a transaction, the scope runs in a savepoint of the transaction
+    let mut transaction = client.transaction().await?;
+    let b = transaction.scoped("tenant_b").await?;
+    Note::create("first note of b").save(&b).await?;
+    assert_eq!(Note::select().execute(&b).await?.len(), 1);
+    b.release().await?;
+    transaction.commit().await?;
+
+    // A scope that is dropped without being released is rolled back
+    {
+        let b = client.scoped("tenant_b").await?;
+        Note::create("lost note of b").save(&b).await?;
+    }
+
+    for tenant in ["tenant_a", "tenant_b"] {
+        let db = client.scoped(tenant).await?;
+        for note in Note::select().execute(&db).await? {
+            println!("{}: {}", db.schema(), note.body);
+        }
+        db.release().await?;
+    }
+
+    Ok(())
+}
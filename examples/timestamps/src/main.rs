@@ -0,0 +1,52 @@
+use ergol::prelude::*;
+use ergol::tokio;
+use ergol::tokio_postgres::NoTls;
+use ergol::Error;
+
+use chrono::{DateTime, Utc};
+
+#[rustfmt::skip]
+#[ergol]
+pub struct Article {
+    #[id] pub id: i32,
+    pub title: String,
+    #[created_at] pub created_at: DateTime<Utc>,
+    #[updated_at] pub updated_at: DateTime<Utc>,
+}
+
+#[rustfmt::skip]
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    ergol::schema::dump_if_requested();
+
+    let (client, connection) = ergol::connect(
+        "host=localhost user=ergol password=ergol",
+        NoTls,
+    )
+    .await?;
+
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            eprintln!("connection error: {}", e);
+        }
+    });
+
+    // Try to delete the database
+    Article::drop_table().execute(&client).await.ok();
+
+    // Create the tables
+    Article::create_table().execute(&client).await?;
+
+    // The timestamps are not part of the create function, they are set by the database
+    let mut article = Article::create("My first article").save(&client).await?;
+    println!("\"{}\" created at {} and updated at {}", article.title, article.created_at, article.updated_at);
+    assert_eq!(article.created_at, article.updated_at);
+
+    // Saving the article refreshes its update timestamp
+    article.title = String::from("My first article (edited)");
+    article.save(&client).await?;
+    println!("\"{}\" created at {} and updated at {}", article.title, article.created_at, article.updated_at);
+    assert!(article.updated_at > article.created_at);
+
+    Ok(())
+}
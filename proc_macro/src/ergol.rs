@@ -1,33 +1,684 @@
-use std::fs::{create_dir_all, File};
-use std::io::Write;
+use std::collections::BTreeMap;
 use std::str::FromStr;
-
-use proc_macro::TokenStream;
+use std::sync::Mutex;
 
 use proc_macro2::TokenStream as TokenStream2;
 
 use syn::parse::{Parse, ParseStream};
-use syn::punctuated::Punctuated;
 use syn::{
-    parenthesized, parse, parse_macro_input, token, Attribute, DeriveInput, Field, FieldsNamed,
-    Ident, Token,
+    parenthesized, Attribute, AttributeArgs, DeriveInput, Error, Field, FieldsNamed, Ident, Lit,
+    Meta, MetaNameValue, NestedMeta, Result, Token,
 };
 
 use quote::{format_ident, quote};
 
-use ergol_core::{Column, Element, Table, Ty};
+use ergol_core::{
+    default_table_name, qualified_name, Column, Constraint, Element, Index, ReferentialAction,
+    Table, Ty,
+};
+
+/// The options given to the `#[ergol(...)]` attribute of a struct.
+#[derive(Default)]
+pub struct Options {
+    /// The name of the table, if it is not the default one.
+    pub table: Option<String>,
+
+    /// The schema in which the table lives, if it is not the current schema.
+    pub schema: Option<String>,
+
+    /// The indexes declared on the struct.
+    pub indexes: Vec<IndexOptions>,
+
+    /// The sets of fields that are unique together.
+    pub uniques: Vec<UniqueOptions>,
+
+    /// The exclusion constraints declared on the struct.
+    pub excludes: Vec<ExcludeOptions>,
+}
+
+/// The arguments of the `#[ergol(...)]` attribute of a struct.
+///
+/// The fields of the `exclude(...)` options are followed by their operators, which are not valid
+/// metas, so those options are parsed apart from the others.
+pub struct Args {
+    /// The options other than `exclude(...)`.
+    pub metas: AttributeArgs,
+
+    /// The `exclude(...)` options.
+    pub excludes: Vec<ExcludeOptions>,
+}
+
+impl Parse for Args {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut args = Args {
+            metas: vec![],
+            excludes: vec![],
+        };
+
+        while !input.is_empty() {
+            let fork = input.fork();
+            let exclude =
+                fork.parse::<Ident>().is_ok_and(|x| x == "exclude") && fork.peek(syn::token::Paren);
+
+            if exclude {
+                let ident: Ident = input.parse()?;
+                let content;
+                parenthesized!(content in input);
+                let options: ExcludeOptions = content.parse()?;
+                if options.elements.is_empty() {
+                    return Err(Error::new(
+                        ident.span(),
+                        "an exclusion constraint needs at least one field",
+                    ));
+                }
+                args.excludes.push(options);
+            } else {
+                args.metas.push(input.parse()?);
+            }
+
+            if input.is_empty() {
+                break;
+            }
+
+            input.parse::<Token![,]>()?;
+        }
+
+        Ok(args)
+    }
+}
+
+impl Options {
+    /// Reads the options from the arguments of the attribute.
+    pub fn from_args(args: Args) -> Result<Options> {
+        let mut options = Options {
+            excludes: args.excludes,
+            ..Options::default()
+        };
+
+        for arg in args.metas {
+            match arg {
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                    path,
+                    lit: Lit::Str(value),
+                    ..
+                })) if path.is_ident("table") => options.table = Some(value.value()),
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                    path,
+                    lit: Lit::Str(value),
+                    ..
+                })) if path.is_ident("schema") => options.schema = Some(value.value()),
+                NestedMeta::Meta(Meta::List(list)) if list.path.is_ident("index") => {
+                    let index = IndexOptions::from_nested(list.nested.clone())?;
+                    if index.fields.is_empty() {
+                        return Err(Error::new_spanned(
+                            list,
+                            "an index of a struct needs at least one field",
+                        ));
+                    }
+                    options.indexes.push(index);
+                }
+                NestedMeta::Meta(Meta::List(list)) if list.path.is_ident("unique") => {
+                    let unique = UniqueOptions::from_nested(list.nested.clone())?;
+                    if unique.fields.is_empty() {
+                        return Err(Error::new_spanned(
+                            list,
+                            "a unique constraint needs at least one field",
+                        ));
+                    }
+                    options.uniques.push(unique);
+                }
+                arg => return Err(Error::new_spanned(
+                    arg,
+                    "unknown ergol option, expected `table = \"...\"`, `schema = \"...\"`, `index(...)`, `unique(...)` or `exclude(...)`",
+                )),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+/// The options of an index, given to the `#[index(...)]` attribute of a field or to the
+/// `index(...)` option of a struct.
+#[derive(Default)]
+pub struct IndexOptions {
+    /// The indexed fields, that are only given to the option of a struct.
+    pub fields: Vec<Ident>,
+
+    /// The name of the index, if it is not the default one.
+    pub name: Option<String>,
+
+    /// Whether the index is unique or not.
+    pub unique: bool,
+
+    /// The index method, if it is not the default one.
+    pub using: Option<String>,
+
+    /// The operator class of the columns, if it is not the default one.
+    pub ops: Option<String>,
+
+    /// The predicate of a partial index.
+    pub predicate: Option<String>,
+}
+
+impl IndexOptions {
+    /// Reads the options of an index.
+    pub fn from_nested<I: IntoIterator<Item = NestedMeta>>(nested: I) -> Result<IndexOptions> {
+        let mut options = IndexOptions::default();
+
+        for arg in nested {
+            match arg {
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("unique") => {
+                    options.unique = true
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.get_ident().is_some() => {
+                    options.fields.push(path.get_ident().unwrap().clone())
+                }
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                    path,
+                    lit: Lit::Str(value),
+                    ..
+                })) if path.is_ident("name") => options.name = Some(value.value()),
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                    path,
+                    lit: Lit::Str(value),
+                    ..
+                })) if path.is_ident("using") => options.using = Some(value.value()),
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                    path,
+                    lit: Lit::Str(value),
+                    ..
+                })) if path.is_ident("ops") => options.ops = Some(value.value()),
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                    path,
+                    lit: Lit::Str(value),
+                    ..
+                })) if path.is_ident("where") => options.predicate = Some(value.value()),
+                arg => return Err(Error::new_spanned(
+                    arg,
+                    "unknown index option, expected fields, `unique`, `name = \"...\"`, `using = \"...\"`, `ops = \"...\"` or `where = \"...\"`",
+                )),
+            }
+        }
+
+        Ok(options)
+    }
+
+    /// Reads the options of the `#[index]` attribute of a field, if any.
+    pub fn from_field(field: &Field) -> Result<Option<IndexOptions>> {
+        let attr = match find_attribute(field, "index") {
+            Some(attr) => attr,
+            None => return Ok(None),
+        };
+
+        let mut options = match attr.parse_meta()? {
+            Meta::Path(_) => IndexOptions::default(),
+            Meta::List(list) => IndexOptions::from_nested(list.nested)?,
+            Meta::NameValue(_) => {
+                return Err(Error::new_spanned(
+                    attr,
+                    "expected `#[index]` or `#[index(...)]`",
+                ))
+            }
+        };
+
+        if !options.fields.is_empty() {
+            return Err(Error::new_spanned(
+                attr,
+                "the index of a field can not have other fields, use the index option of the struct",
+            ));
+        }
+
+        options.fields.push(field.ident.clone().unwrap());
+        Ok(Some(options))
+    }
+
+    /// Returns the index on the table, whose columns are the columns of the fields.
+    pub fn to_index(&self, table_name: &TableName, fields: &FieldsNamed) -> Result<Index> {
+        let columns = self
+            .fields
+            .iter()
+            .map(|ident| find_field(fields, ident).map(column_name))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Index {
+            name: self
+                .name
+                .clone()
+                .unwrap_or_else(|| Index::default_name(&table_name.name, &columns)),
+            columns,
+            unique: self.unique,
+            using: self.using.clone(),
+            ops: self.ops.clone(),
+            predicate: self.predicate.clone(),
+        })
+    }
+}
+
+/// The options of the `unique(...)` option of a struct.
+#[derive(Default)]
+pub struct UniqueOptions {
+    /// The fields that are unique together.
+    pub fields: Vec<Ident>,
+
+    /// The name of the constraint, if it is not the default one.
+    pub name: Option<String>,
+}
+
+impl UniqueOptions {
+    /// Reads the options of a unique constraint.
+    pub fn from_nested<I: IntoIterator<Item = NestedMeta>>(nested: I) -> Result<UniqueOptions> {
+        let mut options = UniqueOptions::default();
+
+        for arg in nested {
+            match arg {
+                NestedMeta::Meta(Meta::Path(path)) if path.get_ident().is_some() => {
+                    options.fields.push(path.get_ident().unwrap().clone())
+                }
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                    path,
+                    lit: Lit::Str(value),
+                    ..
+                })) if path.is_ident("name") => options.name = Some(value.value()),
+                arg => {
+                    return Err(Error::new_spanned(
+                        arg,
+                        "unknown unique option, expected fields or `name = \"...\"`",
+                    ))
+                }
+            }
+        }
+
+        Ok(options)
+    }
+
+    /// Returns the fields that are unique together.
+    pub fn find_fields<'a>(&self, fields: &'a FieldsNamed) -> Result<Vec<&'a Field>> {
+        self.fields
+            .iter()
+            .map(|ident| find_field(fields, ident))
+            .collect()
+    }
+}
+
+/// The options of the `exclude(...)` option of a struct.
+#[derive(Default)]
+pub struct ExcludeOptions {
+    /// The fields of the constraint, with their operators.
+    pub elements: Vec<(Ident, String)>,
+
+    /// The name of the constraint, if it is not the default one.
+    pub name: Option<String>,
+
+    /// The index method of the constraint, if it is not the default one.
+    pub using: Option<String>,
+}
+
+impl Parse for ExcludeOptions {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut options = ExcludeOptions::default();
+
+        while !input.is_empty() {
+            let ident: Ident = input.parse()?;
+
+            if input.peek(Token![=]) {
+                input.parse::<Token![=]>()?;
+                let value: syn::LitStr = input.parse()?;
+                if ident == "name" {
+                    options.name = Some(value.value());
+                } else if ident == "using" {
+                    options.using = Some(value.value());
+                } else {
+                    return Err(Error::new(
+                        ident.span(),
+                        "unknown exclude option, expected `field WITH operator`, `name = \"...\"` or `using = \"...\"`",
+                    ));
+                }
+            } else {
+                let with: Ident = input.parse()?;
+                if with != "WITH" {
+                    return Err(Error::new(with.span(), "expected `WITH`"));
+                }
+
+                // The operator is made of all the tokens up to the next comma, e.g. `&&`.
+                let mut operator = String::new();
+                while !input.is_empty() && !input.peek(Token![,]) {
+                    operator.push_str(&input.parse::<proc_macro2::TokenTree>()?.to_string());
+                }
+
+                if operator.is_empty() {
+                    return Err(Error::new(with.span(), "expected an operator after `WITH`"));
+                }
+
+                options.elements.push((ident, operator));
+            }
+
+            if input.is_empty() {
+                break;
+            }
+
+            input.parse::<Token![,]>()?;
+        }
+
+        Ok(options)
+    }
+}
+
+impl ExcludeOptions {
+    /// Returns the constraint on the table, whose columns are the columns of the fields.
+    pub fn to_constraint(
+        &self,
+        table_name: &TableName,
+        fields: &FieldsNamed,
+    ) -> Result<Constraint> {
+        let elements = self
+            .elements
+            .iter()
+            .map(|(ident, operator)| {
+                find_field(fields, ident).map(|x| (column_name(x), operator.clone()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut constraint = Constraint::exclude(&table_name.name, self.using.clone(), elements);
+        if let Some(name) = &self.name {
+            constraint.name = name.clone();
+        }
+
+        Ok(constraint)
+    }
+}
+
+/// Returns the field with the given name.
+pub fn find_field<'a>(fields: &'a FieldsNamed, ident: &Ident) -> Result<&'a Field> {
+    match fields
+        .named
+        .iter()
+        .find(|x| x.ident.as_ref() == Some(ident))
+    {
+        Some(field) => Ok(field),
+        None => Err(Error::new(
+            ident.span(),
+            format!("unknown field `{}`", ident),
+        )),
+    }
+}
+
+/// Returns the constraints of a struct: the unique and exclusion constraints declared on the
+/// struct, and the `#[check("...")]` attributes of its fields.
+pub fn find_constraints(
+    table_name: &TableName,
+    uniques: &[UniqueOptions],
+    excludes: &[ExcludeOptions],
+    fields: &FieldsNamed,
+) -> Result<Vec<Constraint>> {
+    let mut constraints = uniques
+        .iter()
+        .map(|unique| {
+            let columns = unique
+                .find_fields(fields)?
+                .into_iter()
+                .map(column_name)
+                .collect();
+            let mut constraint = Constraint::unique(&table_name.name, columns);
+            if let Some(name) = &unique.name {
+                constraint.name = name.clone();
+            }
+            Ok(constraint)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    for exclude in excludes {
+        constraints.push(exclude.to_constraint(table_name, fields)?);
+    }
+
+    for field in fields.named.iter() {
+        let checks = field
+            .attrs
+            .iter()
+            .filter(|x| x.path.is_ident("check"))
+            .map(|x| x.parse_args::<syn::LitStr>().map(|x| x.value()))
+            .collect::<Result<Vec<_>>>()?;
+
+        for (i, check) in checks.into_iter().enumerate() {
+            let mut constraint = Constraint::check(&table_name.name, &column_name(field), &check);
+            if i > 0 {
+                constraint.name = format!("{}{}", constraint.name, i);
+            }
+            constraints.push(constraint);
+        }
+    }
+
+    Ok(constraints)
+}
+
+/// Returns all the indexes of a struct, declared on the struct first and then on its fields.
+pub fn find_indexes(
+    table_name: &TableName,
+    indexes: &[IndexOptions],
+    fields: &FieldsNamed,
+) -> Result<Vec<Index>> {
+    let from_fields = fields
+        .named
+        .iter()
+        .map(IndexOptions::from_field)
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+
+    indexes
+        .iter()
+        .chain(from_fields.iter())
+        .map(|x| x.to_index(table_name, fields))
+        .collect()
+}
+
+/// The name of the table of a struct, with the schema in which it lives.
+pub struct TableName {
+    /// The name of the table.
+    pub name: String,
+
+    /// The schema of the table, if it is not the current schema.
+    pub schema: Option<String>,
+}
+
+impl TableName {
+    /// Returns the quoted name of the table, qualified with its schema if any.
+    pub fn qualified(&self) -> String {
+        qualified_name(self.schema.as_deref(), &self.name)
+    }
+
+    /// Returns the name of the join table of a many to many field.
+    pub fn join(&self, field: &Field) -> TableName {
+        TableName {
+            name: format!("{}_{}_join", self.name, field.ident.as_ref().unwrap()),
+            schema: self.schema.clone(),
+        }
+    }
+}
+
+/// Struct to help parse the column attribute.
+struct ColumnName {
+    pub _name: Ident,
+    pub _eq_token: Token![=],
+    pub value: syn::LitStr,
+}
+
+impl Parse for ColumnName {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name: Ident = input.parse()?;
+        if name != "name" {
+            return Err(syn::Error::new(name.span(), "expected `name = \"...\"`"));
+        }
+
+        Ok(ColumnName {
+            _name: name,
+            _eq_token: input.parse()?,
+            value: input.parse()?,
+        })
+    }
+}
+
+/// Checks that the `#[column(name = "...")]` attributes of the fields are well formed.
+pub fn check_column_names(fields: &FieldsNamed) -> Result<()> {
+    for field in fields.named.iter() {
+        if let Some(attr) = find_attribute(field, "column") {
+            attr.parse_args::<ColumnName>()?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the name of the column of a field, which is the name of the field unless it has a
+/// `#[column(name = "...")]` attribute.
+///
+/// The attributes must have been checked by `check_column_names`.
+pub fn column_name(field: &Field) -> String {
+    match find_attribute(field, "column") {
+        Some(attr) => attr.parse_args::<ColumnName>().unwrap().value.value(),
+        None => field.ident.as_ref().unwrap().to_string(),
+    }
+}
+
+/// Returns the names of the methods generated for a relation field, on the struct and on the
+/// referenced struct.
+fn relation_methods(field: &Field) -> Result<Option<(Vec<String>, Vec<String>)>> {
+    use case::CaseExt;
+
+    let ident = field.ident.as_ref().unwrap().to_string();
+
+    // The many to many helpers remove the last letter of the names to add and remove elements.
+    let singular = |name: &str| {
+        let mut name = name.to_owned();
+        name.pop();
+        name
+    };
+
+    if let Some(m) = relation_attribute(field, "many_to_many")? {
+        let reverse = m.names[0].to_string();
+        let updates = m
+            .names
+            .iter()
+            .skip(1)
+            .map(|x| format!("update_{}", x.to_string().to_snake()))
+            .collect::<Vec<_>>();
+
+        let mut own = vec![
+            ident.clone(),
+            format!("add_{}", singular(&ident)),
+            format!("remove_{}", singular(&ident)),
+        ];
+        own.extend(updates.clone());
+
+        let mut other = vec![
+            reverse.clone(),
+            format!("add_{}", singular(&reverse)),
+            format!("remove_{}", singular(&reverse)),
+        ];
+        other.extend(updates);
+
+        Ok(Some((own, other)))
+    } else {
+        let m = match relation_attribute(field, "one_to_one")? {
+            Some(m) => m,
+            None => match relation_attribute(field, "many_to_one")? {
+                Some(m) => m,
+                None => return Ok(None),
+            },
+        };
+        let other = m.names.iter().map(Ident::to_string).collect();
+        Ok(Some((vec![ident], other)))
+    }
+}
+
+/// Fails if the methods or the columns generated for the relations of a struct have the same
+/// names.
+///
+/// Two relations to the same struct must have different reverse names, and the reverse names of
+/// the relations of a struct to itself must be different from the names of its own helpers.
+pub fn check_relation_names(
+    name: &Ident,
+    table_name: &TableName,
+    fields: &FieldsNamed,
+) -> Result<()> {
+    use std::collections::HashMap;
+
+    let name = name.to_string();
+    let mut methods: HashMap<(String, String), String> = HashMap::new();
+
+    for field in fields.named.iter() {
+        let (own, other) = match relation_methods(field)? {
+            Some(methods) => methods,
+            None => continue,
+        };
+
+        let ident = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+        let target = option_inner(ty).unwrap_or(ty);
+        let target = quote! { #target }.to_string();
+
+        if find_attribute(field, "many_to_many").is_some() && *ident == table_name.name {
+            return Err(Error::new(
+                ident.span(),
+                format!(
+                    "the many to many field {} has the name of the table, so both columns of its join table would be named {}_id",
+                    ident, ident
+                ),
+            ));
+        }
+
+        let own = own.into_iter().map(|x| (name.clone(), x));
+        let other = other.into_iter().map(|x| (target.clone(), x));
+
+        for key in own.chain(other) {
+            let message = match methods.insert(key.clone(), ident.to_string()) {
+                Some(previous) if *ident == previous => format!(
+                    "the relation {} generates the method {} twice on {}, use another name in its attribute",
+                    ident, key.1, key.0
+                ),
+                Some(previous) => format!(
+                    "the relations {} and {} both generate a method {} on {}, use different names in their attributes",
+                    previous, ident, key.1, key.0
+                ),
+                None => continue,
+            };
+
+            return Err(Error::new(ident.span(), message));
+        }
+    }
+
+    Ok(())
+}
 
 /// Generates the token stream for an entity.
-pub fn generate(mut input: DeriveInput) -> TokenStream {
-    let mut fields = match &mut input.data {
-        syn::Data::Struct(syn::DataStruct { fields, .. }) => match fields {
-            syn::Fields::Named(fields) => fields,
-            _ => panic!("Expecting named fields"),
-        },
-        _ => panic!("Expecting named fields"),
+pub fn generate(options: Options, mut input: DeriveInput) -> Result<TokenStream2> {
+    let table_name = TableName {
+        name: options
+            .table
+            .unwrap_or_else(|| default_table_name(&input.ident.to_string())),
+        schema: options.schema,
+    };
+
+    let span = input.ident.span();
+
+    let fields = match &mut input.data {
+        syn::Data::Struct(syn::DataStruct {
+            fields: syn::Fields::Named(fields),
+            ..
+        }) => fields,
+        _ => {
+            return Err(Error::new(
+                span,
+                "ergol only supports structs with named fields",
+            ))
+        }
     };
 
-    let to_many_to_many = fix_many_to_many_fields(&input.ident, &fields);
+    check_column_names(fields)?;
+    check_relation_names(&input.ident, &table_name, fields)?;
+
+    let to_many_to_many = fix_many_to_many_fields(&input.ident, &table_name, fields)?;
 
     let clone = fields.named.clone();
     let clone2 = fields.named.clone();
@@ -36,8 +687,49 @@ pub fn generate(mut input: DeriveInput) -> TokenStream {
         .filter(|field| find_attribute(field, "many_to_many").is_some())
         .collect::<Vec<_>>();
 
-    let (field_id, other_fields) = find_id(fields).unwrap();
-    let json = to_json(&input.ident, &field_id, &other_fields);
+    let (field_ids, other_fields) = find_id(fields).ok_or_else(|| {
+        Error::new(
+            span,
+            "missing primary key, mark a field with the #[id] attribute",
+        )
+    })?;
+
+    if let (true, Some(field)) = (field_ids.len() > 1, many_to_many_fields.first()) {
+        return Err(Error::new_spanned(
+            field,
+            "many to many relations are not supported on tables with a composite key",
+        ));
+    }
+
+    let indexes = find_indexes(&table_name, &options.indexes, fields)?;
+
+    let constraints = find_constraints(&table_name, &options.uniques, &options.excludes, fields)?;
+
+    let json = to_json(
+        &input.ident,
+        &table_name,
+        &field_ids,
+        &other_fields,
+        &indexes,
+        &constraints,
+    )?;
+
+    let defined_types = field_ids
+        .iter()
+        .chain(&other_fields)
+        .filter(|field| {
+            find_attribute(field, "one_to_one").is_none()
+                && find_attribute(field, "many_to_one").is_none()
+                && find_attribute(field, "many_to_many").is_none()
+        })
+        .map(|field| assert_defined(&field.ty))
+        .collect::<Vec<_>>();
+
+    // The first element of the json is the table of the struct.
+    let table_json = match &json[0] {
+        Element::Table(table) => table,
+        _ => unreachable!(),
+    };
 
     fields.named.clear();
 
@@ -47,20 +739,43 @@ pub fn generate(mut input: DeriveInput) -> TokenStream {
         }
     }
 
-    let to_one_to_one = fix_one_to_one_fields(&input.ident, &mut fields);
-    let to_many_to_one = fix_many_to_one_fields(&input.ident, &mut fields);
+    let to_one_to_one = fix_one_to_one_fields(&input.ident, &table_name, fields)?;
+    let to_many_to_one = fix_many_to_one_fields(&input.ident, &table_name, fields)?;
 
-    let (field_id, other_fields) = find_id(fields).unwrap();
+    // The fields marked as id are kept by the fixes.
+    let (field_ids, other_fields) = find_id(fields).unwrap();
     let unique_fields = find_unique(fields);
+    let soft_delete = find_soft_delete(fields);
 
     let to_table = to_table(
         &input.ident,
-        &field_id,
+        &table_name,
+        &field_ids,
+        &other_fields,
+        many_to_many_fields.as_slice(),
+        table_json,
+        soft_delete,
+    );
+    let to_impl = to_impl(
+        &input.ident,
+        &table_name,
+        &field_ids,
         &other_fields,
-        &many_to_many_fields.as_slice(),
+        soft_delete,
+    );
+    let composite_uniques = options
+        .uniques
+        .iter()
+        .map(|x| x.find_fields(fields))
+        .collect::<Result<Vec<_>>>()?;
+    let to_unique = to_unique(
+        &input.ident,
+        &table_name,
+        &field_ids,
+        &unique_fields,
+        &composite_uniques,
+        soft_delete,
     );
-    let to_impl = to_impl(&input.ident, &field_id, &other_fields);
-    let to_unique = to_unique(&input.ident, &field_id, &unique_fields);
 
     for field in &mut fields.named {
         field.attrs = field
@@ -74,22 +789,21 @@ pub fn generate(mut input: DeriveInput) -> TokenStream {
                     && s != Some(String::from("one_to_one"))
                     && s != Some(String::from("many_to_one"))
                     && s != Some(String::from("many_to_many"))
+                    && s != Some(String::from("created_at"))
+                    && s != Some(String::from("updated_at"))
+                    && s != Some(String::from("soft_delete"))
+                    && s != Some(String::from("column"))
+                    && s != Some(String::from("index"))
+                    && s != Some(String::from("check"))
+                    && s != Some(String::from("fulltext"))
             })
             .collect();
     }
 
-    // Generate json representation of the table.
-    create_dir_all("migrations/current").unwrap();
-    let mut file = File::create(format!("migrations/current/{}.json", &input.ident)).unwrap();
-    file.write_all(serde_json::to_string_pretty(&json).unwrap().as_bytes())
-        .unwrap();
+    // Register the json representation of the table.
+    let register = register_schema(&input.ident, &json);
 
-    match File::create(format!("migrations/.gitignore")) {
-        Ok(mut f) => f.write_all(b"current\n").unwrap(),
-        _ => (),
-    };
-
-    let q = quote! {
+    Ok(quote! {
         #[derive(Debug)]
         #input
         #to_impl
@@ -98,32 +812,104 @@ pub fn generate(mut input: DeriveInput) -> TokenStream {
         #to_one_to_one
         #to_many_to_one
         #to_many_to_many
-    };
+        #(#defined_types)*
+        #register
+    })
+}
+
+/// The types defined by the derives of the program, by the name of their rust type.
+///
+/// The macros of a crate are expanded in the same process, so the columns of a struct can use
+/// the types whose derive comes before it.
+static TYPES: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+
+/// Registers the json representation of the elements of a struct or an enum in the schema of the
+/// program, and the types it defines for the columns of the structs that follow.
+pub fn register_schema(name: &Ident, json: &[Element]) -> TokenStream2 {
+    let name = name.to_string();
+
+    for element in json {
+        let type_name = match element {
+            Element::Enum(e) => &e.name,
+            Element::Domain(d) => &d.name,
+            Element::Alias(a) => &a.name,
+            _ => continue,
+        };
+
+        TYPES
+            .lock()
+            .unwrap()
+            .insert(name.clone(), type_name.clone());
+    }
+
+    let json = serde_json::to_string_pretty(json).unwrap();
 
-    q.into()
+    quote! {
+        ergol::inventory::submit! {
+            ergol::schema::Entry { name: #name, json: #json }
+        }
+    }
 }
 
-/// Finds the field marked as id in a fieldsnamed.
-pub fn find_id(fields: &FieldsNamed) -> Option<(&Field, Vec<&Field>)> {
+/// Finds the fields marked as id in a fieldsnamed.
+///
+/// There can be several of them, in which case the primary key of the table is composite.
+pub fn find_id(fields: &FieldsNamed) -> Option<(Vec<&Field>, Vec<&Field>)> {
     let mut other_fields = vec![];
-    let mut id = None;
+    let mut ids = vec![];
 
-    'outer: for field in fields.named.iter() {
-        for attr in &field.attrs {
-            match attr.path.get_ident() {
-                Some(i) if &i.to_string() == "id" && id.is_some() => return None,
-                Some(i) if &i.to_string() == "id" => {
-                    id = Some(field);
-                    continue 'outer;
-                }
-                _ => (),
-            }
+    for field in fields.named.iter() {
+        if find_attribute(field, "id").is_some() {
+            ids.push(field);
+        } else {
+            other_fields.push(field);
         }
-        other_fields.push(field);
     }
 
-    match id {
-        Some(id) => Some((id, other_fields)),
+    if ids.is_empty() {
+        None
+    } else {
+        Some((ids, other_fields))
+    }
+}
+
+/// The different kinds of primary keys.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Key {
+    /// An i32 key generated by the database.
+    Serial,
+
+    /// An i64 key generated by the database.
+    BigSerial,
+
+    /// A uuid key generated by the database.
+    Uuid,
+
+    /// A key whose value is given by the user.
+    Natural,
+}
+
+impl Key {
+    /// Finds the kind of primary key from the type of the field marked as id.
+    pub fn from_field(field: &Field) -> Key {
+        match type_name(&field.ty).as_deref() {
+            Some("i32") => Key::Serial,
+            Some("i64") => Key::BigSerial,
+            Some("Uuid") => Key::Uuid,
+            _ => Key::Natural,
+        }
+    }
+
+    /// Returns whether the value of the key is generated by the database.
+    pub fn is_generated(self) -> bool {
+        self != Key::Natural
+    }
+}
+
+/// Returns the name of a type without its path, e.g. `Uuid` for `uuid::Uuid`.
+pub fn type_name(ty: &syn::Type) -> Option<String> {
+    match ty {
+        syn::Type::Path(path) => path.path.segments.last().map(|x| x.ident.to_string()),
         _ => None,
     }
 }
@@ -147,6 +933,67 @@ pub fn find_unique(fields: &FieldsNamed) -> Vec<&Field> {
     output
 }
 
+/// Finds the field marked as soft_delete.
+pub fn find_soft_delete(fields: &FieldsNamed) -> Option<&Field> {
+    fields
+        .named
+        .iter()
+        .find(|field| find_attribute(field, "soft_delete").is_some())
+}
+
+/// Returns the SQL condition that excludes the soft deleted rows of a table, if the table has a
+/// soft_delete field.
+pub fn not_deleted(table_name: &TableName, soft_delete: Option<&Field>) -> String {
+    match soft_delete {
+        Some(field) => format!(
+            " AND {}.\"{}\" IS NULL",
+            table_name.qualified(),
+            column_name(field)
+        ),
+        None => String::new(),
+    }
+}
+
+/// Helper to find whether a field is a timestamp automatically managed by the database.
+pub fn is_timestamp(field: &Field) -> bool {
+    find_attribute(field, "created_at").is_some() || find_attribute(field, "updated_at").is_some()
+}
+
+/// Returns the first type argument of a type, e.g. `User` for `ManyToOne<User>`.
+pub fn first_type_argument(ty: &syn::Type) -> Option<&syn::Type> {
+    match ty {
+        syn::Type::Path(path) => match &path.path.segments.last()?.arguments {
+            syn::PathArguments::AngleBracketed(args) => match args.args.first()? {
+                syn::GenericArgument::Type(ty) => Some(ty),
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Returns the type inside an option, if the type is an option.
+pub fn option_inner(ty: &syn::Type) -> Option<&syn::Type> {
+    match ty {
+        syn::Type::Path(path) if path.path.segments.last()?.ident == "Option" => {
+            first_type_argument(ty)
+        }
+        _ => None,
+    }
+}
+
+/// Returns the type of the referenced element if the field is an optional many to one relation.
+///
+/// The field must have been fixed, i.e. its type is `Option<ManyToOne<T>>`.
+pub fn optional_relation(field: &Field) -> Option<&syn::Type> {
+    if find_attribute(field, "many_to_one").is_some() {
+        option_inner(&field.ty).and_then(first_type_argument)
+    } else {
+        None
+    }
+}
+
 /// Helper to find whether a field has a specific attribute.
 pub fn find_attribute<'a>(field: &'a Field, attr: &str) -> Option<&'a Attribute> {
     field
@@ -155,47 +1002,179 @@ pub fn find_attribute<'a>(field: &'a Field, attr: &str) -> Option<&'a Attribute>
         .find(|x| x.path.get_ident().map(Ident::to_string) == Some(String::from(attr)))
 }
 
-/// Generates the json.
-pub fn to_json(name: &Ident, id: &Field, other_fields: &[&Field]) -> Vec<Element> {
+/// Returns the text search configuration given by the `#[fulltext(lang = "...")]` attribute of a
+/// field, if any, which is `simple` for the `#[fulltext]` attribute.
+pub fn fulltext_lang(field: &Field) -> Result<Option<String>> {
+    let attr = match find_attribute(field, "fulltext") {
+        Some(attr) => attr,
+        None => return Ok(None),
+    };
+
+    let lang = match attr.parse_meta()? {
+        Meta::Path(_) => String::from("simple"),
+        Meta::List(list) => match list.nested.iter().collect::<Vec<_>>()[..] {
+            [NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                path,
+                lit: Lit::Str(value),
+                ..
+            }))] if path.is_ident("lang") => value.value(),
+            _ => {
+                return Err(Error::new_spanned(
+                    attr,
+                    "expected `#[fulltext]` or `#[fulltext(lang = \"...\")]`",
+                ))
+            }
+        },
+        Meta::NameValue(_) => {
+            return Err(Error::new_spanned(
+                attr,
+                "expected `#[fulltext]` or `#[fulltext(lang = \"...\")]`",
+            ))
+        }
+    };
+
+    if *column_ty(&field.ty)?.inner() != Ty::String {
+        return Err(Error::new_spanned(
+            &field.ty,
+            "the fulltext attribute requires a `String` or an `Option<String>` field",
+        ));
+    }
+
+    Ok(Some(lang))
+}
+
+/// Returns the type of the column that stores a rust type.
+pub fn column_ty(ty: &syn::Type) -> Result<Ty> {
     use case::CaseExt;
+    let name = quote! { #ty }.to_string();
+    let types = TYPES.lock().unwrap().clone();
 
-    let name_snake = format_ident!("{}", name.to_string().to_snake());
-    let table_name = format_ident!("{}s", name_snake);
-    let table_name_format = format!("{}", table_name);
-    let id_ident = id.ident.as_ref().unwrap();
-    let id_name = format_ident!("{}", id_ident.to_string());
+    // The types that are not known yet are types of the program, which `assert_defined` checks.
+    let others = program_types(ty)
+        .into_iter()
+        .filter_map(|x| match x {
+            syn::Type::Path(path) => Some(path.path.segments.last()?.ident.to_string()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
 
-    let mut output = vec![];
-    let mut json = Table::new(&table_name_format);
+    let program_type = |x: &str| {
+        types
+            .get(x)
+            .cloned()
+            .or_else(|| others.iter().find(|y| *y == x).map(|_| x.to_snake()))
+    };
 
-    json.columns
-        .push(Column::new(&format!("{}", id_name), Ty::Id, false));
+    Ty::parse(&name, &program_type)
+        .ok_or_else(|| Error::new_spanned(ty, format!("the type {} can not be stored", name)))
+}
+
+/// Returns the types of the program in a type of column, e.g. `Role` in `Option<Vec<Role>>`.
+fn program_types(ty: &syn::Type) -> Vec<&syn::Type> {
+    if Ty::from_str(&quote! { #ty }.to_string()).is_ok() {
+        return vec![];
+    }
+
+    match ty {
+        syn::Type::Path(path) => match path.path.segments.last().map(|x| &x.arguments) {
+            Some(syn::PathArguments::AngleBracketed(args)) => args
+                .args
+                .iter()
+                .filter_map(|x| match x {
+                    syn::GenericArgument::Type(ty) => Some(ty),
+                    _ => None,
+                })
+                .flat_map(program_types)
+                .collect(),
+            _ => vec![ty],
+        },
+        _ => vec![],
+    }
+}
+
+/// Generates the assertions that the types of the program used in a column derive `PgEnum`,
+/// `PgJson` or `PgType`, since the macro only knows their names.
+pub fn assert_defined(ty: &syn::Type) -> TokenStream2 {
+    let types = program_types(ty);
+
+    quote! {
+        #(
+            const _: fn() = || {
+                fn assert_defined<T: ergol::pg::Defined>() {}
+                assert_defined::<#types>();
+            };
+        )*
+    }
+}
+
+/// Generates the json column of a primary key made of a single field.
+pub fn key_column(id: &Field) -> Result<Column> {
+    let id_name = column_name(id);
+
+    Ok(match Key::from_field(id) {
+        Key::Serial => Column::new(&id_name, Ty::Id, false),
+        Key::BigSerial => Column::new(&id_name, Ty::BigId, false),
+        Key::Uuid => Column::new(&id_name, Ty::PrimaryKey(Box::new(Ty::Uuid)), false)
+            .with_default("gen_random_uuid()"),
+        Key::Natural => Column::new(
+            &id_name,
+            Ty::PrimaryKey(Box::new(column_ty(&id.ty)?)),
+            false,
+        ),
+    })
+}
+
+/// Generates the json.
+pub fn to_json(
+    entity: &Ident,
+    table_name: &TableName,
+    ids: &[&Field],
+    other_fields: &[&Field],
+    indexes: &[Index],
+    constraints: &[Constraint],
+) -> Result<Vec<Element>> {
+    use case::CaseExt;
+
+    let mut output = vec![];
+    let mut json = Table::new(&table_name.name);
+    json.schema = table_name.schema.clone();
+    json.indexes = indexes.to_vec();
+    json.constraints = constraints.to_vec();
+
+    match ids {
+        [id] => json.columns.push(key_column(id)?),
+        _ => {
+            // The columns of a composite key are never generated by the database.
+            for id in ids {
+                let id_name = column_name(id);
+                json.columns
+                    .push(Column::new(&id_name, column_ty(&id.ty)?, false));
+                json.primary_key.push(id_name);
+            }
+        }
+    }
 
     for field in other_fields {
         let ty = &field.ty;
 
-        if let Some(attr) = find_attribute(field, "many_to_many") {
-            let tokens = Into::<TokenStream>::into(attr.tokens.clone());
-            let m = parse::<MappedBy>(tokens).unwrap();
+        if let Some(m) = relation_attribute(field, "many_to_many")? {
             let extras = m.names.into_iter().skip(1).collect::<Vec<_>>();
-            let mut table = Table::new(&format!(
-                "{}_{}_join",
-                table_name,
-                format_ident!("{}", field.ident.as_ref().unwrap())
-            ));
+            let join = table_name.join(field);
+            let mut table = Table::new(&join.name);
+            table.schema = join.schema;
 
             // Primary key of table
             table.columns.push(Column::new("id", Ty::Id, false));
 
             // Id of the first link
             table.columns.push(Column::new(
-                &format!("{}_id", table_name_format),
-                Ty::Reference(table_name_format.clone()),
+                &format!("{}_id", table_name.name),
+                Ty::Reference(default_table_name(&entity.to_string())),
                 false,
             ));
 
             // Id of the second link
-            let name = format!("{}s", quote! {#ty}.to_string().to_snake());
+            let name = default_table_name(&quote! {#ty}.to_string());
             table.columns.push(Column::new(
                 &format!("{}_id", field.ident.as_ref().unwrap()),
                 Ty::Reference(name),
@@ -207,68 +1186,256 @@ pub fn to_json(name: &Ident, id: &Field, other_fields: &[&Field]) -> Vec<Element
                 let e = extra.to_string().to_snake();
                 table
                     .columns
-                    .push(Column::new(&e, Ty::from_str(&e).unwrap(), false));
+                    .push(Column::new(&e, Ty::Enum(e.clone()), false));
             }
 
             output.push(Element::Table(table));
         } else if find_attribute(field, "one_to_one").is_some()
             || find_attribute(field, "many_to_one").is_some()
         {
-            json.columns.push(Column::new(
-                &format!("{}", field.ident.as_ref().unwrap()),
-                Ty::Reference(format!("{}s", quote! { #ty }).to_snake()),
-                false,
-            ));
+            let (on_delete, on_update) = referential_actions(field)?;
+            let set_null = [on_delete, on_update].contains(&Some(ReferentialAction::SetNull));
+
+            if set_null && option_inner(ty).is_none() {
+                return Err(Error::new_spanned(
+                    ty,
+                    "the set_null action requires an optional relation",
+                ));
+            }
+
+            let column_ty = match option_inner(ty) {
+                Some(inner) => Ty::Option(Box::new(Ty::Reference(default_table_name(
+                    &quote! { #inner }.to_string(),
+                )))),
+                None => Ty::Reference(default_table_name(&quote! { #ty }.to_string())),
+            };
+
+            let mut column = Column::new(&column_name(field), column_ty, false);
+            column.on_delete = on_delete;
+            column.on_update = on_update;
+            json.columns.push(column);
         } else {
-            json.columns.push(Column::new(
-                &format!("{}", field.ident.as_ref().unwrap()),
-                Ty::from_str(&format!("{}", quote! { #ty })).unwrap(),
+            let column = Column::new(
+                &column_name(field),
+                column_ty(ty)?,
                 find_attribute(field, "unique").is_some(),
-            ));
+            );
+
+            json.columns.push(if is_timestamp(field) {
+                column.with_default("now()")
+            } else {
+                column
+            });
+        }
+    }
+
+    // The generated columns are after the columns of the fields, so that the rows are read with the
+    // positions of the fields.
+    for field in other_fields {
+        if let Some(lang) = fulltext_lang(field)? {
+            let column = Column::fulltext(&column_name(field), &lang);
+            let columns = vec![column.name.clone()];
+            json.indexes.push(Index {
+                name: Index::default_name(&table_name.name, &columns),
+                columns,
+                unique: false,
+                using: Some(String::from("gin")),
+                ops: None,
+                predicate: None,
+            });
+            json.columns.push(column);
         }
     }
 
     output.insert(0, Element::Table(json));
-    output
+    Ok(output)
+}
+
+/// Generates the function of the module of a column that filters the results with a binary
+/// operator, given by the name of its variant in `ergol::query::Operator`.
+fn binary_filter(column: &str, name: &str, operator: &str, doc: &str) -> TokenStream2 {
+    let name = format_ident!("{}", name);
+    let operator = format_ident!("{}", operator);
+
+    quote! {
+        #[doc = #doc]
+        pub fn #name<T: ergol::tokio_postgres::types::ToSql + Sync + Send + 'static>(t: T) -> ergol::query::Filter {
+            ergol::query::Filter::Binary {
+                column: #column,
+                value: Box::new(t),
+                operator: ergol::query::Operator::#operator,
+            }
+        }
+    }
+}
+
+/// Generates the filters of the columns whose type, without its option, matches a predicate.
+///
+/// The binary filters are given by their names, their operators and their docs, and the other
+/// functions of a column are generated from its name.
+fn typed_filters(
+    json: &Table,
+    columns: &[String],
+    matches: impl Fn(&Ty) -> bool,
+    binaries: &[(&str, &str, &str)],
+    others: impl Fn(&str) -> TokenStream2,
+) -> Vec<TokenStream2> {
+    columns
+        .iter()
+        .map(|x| {
+            if !json
+                .columns
+                .iter()
+                .any(|c| &c.name == x && matches(c.ty.inner()))
+            {
+                return quote! {};
+            }
+
+            let binaries = binaries
+                .iter()
+                .map(|(name, operator, doc)| binary_filter(x, name, operator, doc));
+            let others = others(x);
+
+            quote! {
+                #(#binaries)*
+                #others
+            }
+        })
+        .collect()
 }
 
 /// Generates the ToTable implementation.
+///
+/// The constraints and the indexes of the table are read from its json description.
 pub fn to_table(
     name: &Ident,
-    id: &Field,
+    table_name: &TableName,
+    ids: &[&Field],
     other_fields: &[&Field],
     many_to_many_fields: &[&Field],
+    json: &Table,
+    soft_delete: Option<&Field>,
 ) -> TokenStream2 {
     use case::CaseExt;
 
     let name_snake = format_ident!("{}", name.to_string().to_snake());
-    let table_name = format_ident!("{}s", name_snake);
-    let id_ident = id.ident.as_ref().unwrap();
-    let id_name = format_ident!("{}", id_ident.to_string());
+    let id_names = ids
+        .iter()
+        .map(|id| id.ident.as_ref().unwrap())
+        .collect::<Vec<_>>();
+    let id_columns = ids.iter().map(|id| column_name(id)).collect::<Vec<_>>();
+    let id_indices = (0..ids.len()).map(syn::Index::from);
 
     let row = quote!(ergol::tokio_postgres::Row);
 
+    let mut key_types = vec![];
+
     let mut create_table = vec![];
-    create_table.push(format!("CREATE TABLE \"{}\" (\n", table_name));
-    create_table.push(format!("    \"{}\" SERIAL PRIMARY KEY,\n", id_name));
+    create_table.push(format!("CREATE TABLE {} (\n", table_name.qualified()));
+
+    let (id_ty, id_name, id) = match ids {
+        [id] => {
+            let id_ident = id.ident.as_ref().unwrap();
+            let id_name = column_name(id);
+            let id_ty = &id.ty;
+
+            create_table.push(match Key::from_field(id) {
+                Key::Serial => format!("    \"{}\" SERIAL PRIMARY KEY,\n", id_name),
+                Key::BigSerial => format!("    \"{}\" BIGSERIAL PRIMARY KEY,\n", id_name),
+                Key::Uuid => {
+                    key_types.push(id_ty);
+                    format!(
+                        "    \"{}\" {{}} PRIMARY KEY DEFAULT gen_random_uuid(),\n",
+                        id_name
+                    )
+                }
+                Key::Natural => {
+                    key_types.push(id_ty);
+                    format!("    \"{}\" {{}} PRIMARY KEY,\n", id_name)
+                }
+            });
+
+            (
+                quote! { #id_ty },
+                id_name,
+                quote! { Clone::clone(&self.#id_ident) },
+            )
+        }
+        _ => {
+            for id in ids {
+                key_types.push(&id.ty);
+                create_table.push(format!("    \"{}\" {{}},\n", column_name(id)));
+            }
+
+            let id_types = ids.iter().map(|id| &id.ty);
+            let id_names = id_names.iter();
+
+            (
+                quote! { (#(#id_types),*) },
+                id_columns.join(", "),
+                quote! { (#(Clone::clone(&self.#id_names)),*) },
+            )
+        }
+    };
 
     let mut field_types = vec![];
     let mut field_names = vec![];
-    let field_indices = (1..other_fields.len() + 1).map(syn::Index::from);
+    let mut field_columns = vec![];
+    let field_indices = (ids.len()..ids.len() + other_fields.len()).map(syn::Index::from);
 
     for field in other_fields {
+        let column = column_name(field);
+
         create_table.push(format!(
-            "    \"{}\" {{}}{},\n",
-            field.ident.as_ref().unwrap().to_string(),
+            "    \"{}\" {{}}{}{}{},\n",
+            column,
+            // The referential actions of the relations are read from the json columns.
+            json.columns
+                .iter()
+                .find(|x| x.name == column)
+                .map(Column::actions)
+                .unwrap_or_default(),
             if find_attribute(field, "unique").is_some() {
                 " UNIQUE"
             } else {
                 ""
+            },
+            if is_timestamp(field) {
+                " DEFAULT now()"
+            } else {
+                ""
             }
         ));
 
         field_types.push(&field.ty);
         field_names.push(&field.ident);
+        field_columns.push(column);
+    }
+
+    for column in json.columns.iter().filter(|x| x.generated.is_some()) {
+        // The create table query is a format string.
+        let column = column.to_postgres().replace('{', "{{").replace('}', "}}");
+        create_table.push(format!("    {},\n", column));
+    }
+
+    if ids.len() > 1 {
+        create_table.push(format!(
+            "    PRIMARY KEY ({}),\n",
+            id_columns
+                .iter()
+                .map(|x| format!("\"{}\"", x))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+
+    for constraint in &json.constraints {
+        // The create table query is a format string.
+        let constraint = constraint
+            .to_postgres()
+            .replace('{', "{{")
+            .replace('}', "}}");
+        create_table.push(format!("    {},\n", constraint));
     }
 
     let mut create_table = create_table.join("");
@@ -276,14 +1443,11 @@ pub fn to_table(
     create_table.pop();
     create_table.push_str("\n);");
 
-    let extra = many_to_many_fields
-        .iter()
-        .map(|x| find_attribute(x, "many_to_many").unwrap())
-        .map(|x| Into::<TokenStream>::into(x.tokens.clone()))
-        .map(|tokens| {
-            let m = parse::<MappedBy>(tokens).unwrap();
-            m.names.into_iter().skip(1).collect::<Vec<_>>()
-        });
+    // The attributes of the many to many fields have been checked by `fix_many_to_many_fields`.
+    let extra = many_to_many_fields.iter().map(|x| {
+        let m = relation_attribute(x, "many_to_many").unwrap().unwrap();
+        m.names.into_iter().skip(1).collect::<Vec<_>>()
+    });
 
     let create_tables = many_to_many_fields
         .iter()
@@ -291,25 +1455,22 @@ pub fn to_table(
         .map(|(field, extra)| {
             let mut new = vec![];
             new.push(format!(
-                "CREATE TABLE \"{}_{}_join\" (\n",
-                table_name,
-                format_ident!("{}", field.ident.as_ref().unwrap())
+                "CREATE TABLE {} (\n",
+                table_name.join(field).qualified()
             ));
 
-            new.push(format!("    \"id\" SERIAL PRIMARY KEY,\n"));
+            new.push("    \"id\" SERIAL PRIMARY KEY,\n".to_string());
 
             new.push(format!(
-                "    \"{}_id\" INT NOT NULL REFERENCES \"{}\" ON DELETE CASCADE,\n",
-                table_name, table_name,
+                "    \"{}_id\" {{}} REFERENCES {} ON DELETE CASCADE,\n",
+                table_name.name,
+                table_name.qualified(),
             ));
 
-            let ty = &field.ty;
-            let name = format!("{}s", quote! {#ty}.to_string().to_snake());
-
+            // The name of the referenced table is only known at runtime.
             new.push(format!(
-                "    \"{}_id\" INT NOT NULL REFERENCES \"{}\" ON DELETE CASCADE,\n",
+                "    \"{}_id\" {{}} REFERENCES {{}} ON DELETE CASCADE,\n",
                 field.ident.as_ref().unwrap(),
-                name,
             ));
 
             for extra in extra {
@@ -322,44 +1483,227 @@ pub fn to_table(
             new.pop();
             new.push_str("\n);");
 
-            new
+            new
+        })
+        .collect::<Vec<_>>();
+
+    let many_to_many_types = many_to_many_fields.iter().map(|field| &field.ty);
+
+    let create_indexes = json
+        .indexes
+        .iter()
+        .map(|x| x.create_index(&table_name.qualified(), false))
+        .collect::<Vec<_>>();
+
+    let mut drop_tables = vec![format!("DROP TABLE {} CASCADE;", table_name.qualified())];
+
+    for field in many_to_many_fields {
+        drop_tables.push(format!(
+            "DROP TABLE {} CASCADE;",
+            table_name.join(field).qualified()
+        ));
+    }
+
+    let field_names = field_names.iter();
+    let field_names2 = field_names.clone();
+
+    let table = &table_name.name;
+
+    let (schema_name, create_schema) = match &table_name.schema {
+        Some(schema) => (
+            quote! {
+                fn schema_name() -> Option<&'static str> {
+                    Some(#schema)
+                }
+            },
+            vec![format!("CREATE SCHEMA IF NOT EXISTS \"{}\";", schema)],
+        ),
+        None => (quote! {}, vec![]),
+    };
+
+    let soft_delete_name = match soft_delete {
+        Some(field) => {
+            let soft_delete = column_name(field);
+            quote! {
+                fn soft_delete_name() -> Option<&'static str> {
+                    Some(#soft_delete)
+                }
+            }
+        }
+        None => quote! {},
+    };
+
+    let field_likes = field_columns
+        .iter()
+        .zip(field_types.clone())
+        .map(|(x, y)| {
+            if quote! { #y }.to_string() == "String" {
+                let like = binary_filter(x, "like", "Like", "Construct a like query.");
+                let similar_to = binary_filter(
+                    x,
+                    "similar_to",
+                    "SimilarTo",
+                    "Construct a similar to query.",
+                );
+                quote! { #like #similar_to }
+            } else {
+                quote! {}
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let field_arrays = typed_filters(
+        json,
+        &field_columns,
+        |ty| matches!(ty, Ty::Array(_)),
+        &[
+            (
+                "contains",
+                "Contains",
+                "Keeps only the results for which the array of the column contains all the elements of the array passed as parameter.",
+            ),
+            (
+                "overlaps",
+                "Overlaps",
+                "Keeps only the results for which the array of the column has elements in common with the array passed as parameter.",
+            ),
+        ],
+        |x| {
+            quote! {
+                /// Keeps only the results for which the array of the column contains the
+                /// value passed as parameter.
+                pub fn any<T: ergol::tokio_postgres::types::ToSql + Sync + Send + 'static>(t: T) -> ergol::query::Filter {
+                    ergol::query::Filter::Any {
+                        column: #x,
+                        value: Box::new(t),
+                    }
+                }
+            }
+        },
+    );
+
+    let field_ranges = typed_filters(
+        json,
+        &field_columns,
+        |ty| matches!(ty, Ty::Range(_)),
+        &[
+            (
+                "contains",
+                "Contains",
+                "Keeps only the results for which the range of the column contains the range passed as parameter.",
+            ),
+            (
+                "overlaps",
+                "Overlaps",
+                "Keeps only the results for which the range of the column has values in common with the range passed as parameter.",
+            ),
+            (
+                "adjacent",
+                "Adjacent",
+                "Keeps only the results for which the range of the column is adjacent to the range passed as parameter.",
+            ),
+        ],
+        |_| quote! {},
+    );
+
+    // The attributes have been checked by `to_json`.
+    let field_fulltexts = other_fields
+        .iter()
+        .map(|field| {
+            let lang = match fulltext_lang(field).ok().flatten() {
+                Some(lang) => lang,
+                None => return quote! {},
+            };
+
+            let column = Column::fulltext_name(&column_name(field));
+
+            quote! {
+                /// Keeps only the results whose text matches the full text search query passed as
+                /// parameter, written with the syntax of web search engines.
+                pub fn matches<T: ergol::tokio_postgres::types::ToSql + Sync + Send + 'static>(t: T) -> ergol::query::Filter {
+                    ergol::query::Filter::Search {
+                        column: #column,
+                        lang: #lang,
+                        value: Box::new(t),
+                    }
+                }
+
+                /// Sorts the results according to their rank for the full text search query passed
+                /// as parameter, the most relevant first.
+                pub fn rank<T: ergol::tokio_postgres::types::ToSql + Sync + Send + 'static>(t: T) -> ergol::query::OrderBy {
+                    ergol::query::OrderBy {
+                        column: #column,
+                        order: ergol::query::Order::Descend,
+                        rank: Some(ergol::query::Rank {
+                            lang: #lang,
+                            value: Box::new(t),
+                        }),
+                    }
+                }
+            }
         })
         .collect::<Vec<_>>();
 
-    let mut drop_tables = vec![format!("DROP TABLE \"{}\" CASCADE;", table_name)];
+    let field_jsons = typed_filters(
+        json,
+        &field_columns,
+        |ty| *ty == Ty::Jsonb,
+        &[
+            (
+                "contains",
+                "Contains",
+                "Keeps only the results for which the json value of the column contains the json value passed as parameter.",
+            ),
+            (
+                "has_key",
+                "HasKey",
+                "Keeps only the results for which the json value of the column has the key passed as parameter.",
+            ),
+        ],
+        |x| {
+            quote! {
+                /// Returns the field of the json value of the column with the key passed as
+                /// parameter, compared as json (`->`).
+                pub fn field(key: &str) -> ergol::query::JsonField {
+                    ergol::query::JsonField {
+                        column: #x,
+                        key: key.to_owned(),
+                        text: false,
+                    }
+                }
 
-    for field in many_to_many_fields {
-        drop_tables.push(format!(
-            "DROP TABLE \"{}_{}_join\" CASCADE;",
-            table_name,
-            format_ident!("{}", field.ident.as_ref().unwrap())
-        ));
-    }
+                /// Returns the field of the json value of the column with the key passed as
+                /// parameter, compared as text (`->>`).
+                pub fn field_text(key: &str) -> ergol::query::JsonField {
+                    ergol::query::JsonField {
+                        column: #x,
+                        key: key.to_owned(),
+                        text: true,
+                    }
+                }
+            }
+        },
+    );
 
-    let field_names = field_names.iter();
-    let field_names2 = field_names.clone();
+    // The columns whose type is defined by the program can be filtered by tag, if the type is
+    // stored as json.
+    let field_tags = field_columns
+        .iter()
+        .map(|x| {
+            let user_type = json
+                .columns
+                .iter()
+                .any(|c| &c.name == x && matches!(c.ty.inner(), Ty::Enum(_)));
 
-    let field_likes = field_names2
-        .clone()
-        .zip(field_types.clone())
-        .map(|(x, y)| {
-            if quote! { #y }.to_string() == "String" {
+            if user_type {
                 quote! {
-                    /// Construct a like query.
-                    pub fn like<T: ergol::tokio_postgres::types::ToSql + Sync + Send + 'static>(t: T) -> ergol::query::Filter {
-                        ergol::query::Filter::Binary {
-                            column: stringify!(#x),
+                    /// Keeps only the results for which the variant of the value of the column,
+                    /// stored as json, is the one passed as parameter.
+                    pub fn has_tag<T: ergol::tokio_postgres::types::ToSql + Sync + Send + 'static>(t: T) -> ergol::query::Filter {
+                        ergol::query::Filter::Tag {
+                            column: #x,
+                            key: None,
                             value: Box::new(t),
-                            operator: ergol::query::Operator::Like,
-                        }
-                    }
-
-                    /// Construct a similar to query.
-                    pub fn similar_to<T: ergol::tokio_postgres::types::ToSql + Sync + Send + 'static>(t: T) -> ergol::query::Filter {
-                        ergol::query::Filter::Binary {
-                            column: stringify!(#x),
-                            value: Box::new(t),
-                            operator: ergol::query::Operator::SimilarTo,
                         }
                     }
                 }
@@ -369,34 +1713,73 @@ pub fn to_table(
         })
         .collect::<Vec<_>>();
 
+    // The generated columns are read by the queries that select all the columns.
+    let column_count = json.columns.len();
+
     let tokens = quote! {
         impl ergol::ToTable for #name {
-            fn from_row_with_offset(row: &#row, offset: usize) -> Self {
-                #name {
-                    #id_ident: row.get(offset),
+            type Id = #id_ty;
+
+            fn from_row_with_offset(row: &#row, offset: usize) -> std::result::Result<Self, ergol::Error> {
+                Ok(#name {
                     #(
-                        #field_names: row.get(offset + #field_indices),
+                        #id_names: row
+                            .try_get(offset + #id_indices)
+                            .map_err(|e| ergol::Error::decode(#id_columns, e))?,
                     )*
-                }
+                    #(
+                        #field_names: row
+                            .try_get(offset + #field_indices)
+                            .map_err(|e| ergol::Error::decode(#field_columns, e))?,
+                    )*
+                })
+            }
+
+            fn column_count() -> usize {
+                #column_count
             }
 
             fn table_name() -> &'static str {
-                stringify!(#table_name)
+                #table
             }
 
+            #schema_name
+
             fn id_name() -> &'static str {
-                stringify!(#id_name)
+                #id_name
+            }
+
+            fn id(&self) -> Self::Id {
+                #id
             }
 
-            fn id(&self) -> i32 {
-                self.#id_ident
+            #soft_delete_name
+
+            fn column_tag(column: &str) -> Option<&'static str> {
+                match column {
+                    #(
+                        #field_columns => <#field_types as Pg>::tag(),
+                    )*
+                    _ => None,
+                }
             }
 
             fn create_table() -> ergol::query::CreateTable {
                 ergol::query::CreateTable(vec![
-                    format!(#create_table, #(<#field_types as Pg>::ty(), )*),
                     #(
-                        String::from(#create_tables),
+                        String::from(#create_schema),
+                    )*
+                    format!(#create_table, #(<#key_types as Pg>::ty(), )* #(<#field_types as Pg>::ty(), )*),
+                    #(
+                        format!(
+                            #create_tables,
+                            <<Self as ergol::ToTable>::Id as Pg>::ty(),
+                            <<#many_to_many_types as ergol::ToTable>::Id as Pg>::ty(),
+                            <#many_to_many_types as ergol::ToTable>::qualified_table_name(),
+                        ),
+                    )*
+                    #(
+                        String::from(#create_indexes),
                     )*
                 ])
             }
@@ -417,13 +1800,17 @@ pub fn to_table(
         /// Module that contains the columns of the table.
         pub mod #name_snake {
 
+            #(
             /// Module that contains the helpers for the column.
-            pub mod #id_name {
+            pub mod #id_names {
+                /// The name of the column.
+                pub const COLUMN: &str = #id_columns;
+
                 /// Keeps only the results for which the column equals the value passed as
                 /// parameter.
                 pub fn eq<T: ergol::tokio_postgres::types::ToSql + Sync + Send + 'static>(t: T) -> ergol::query::Filter {
                     ergol::query::Filter::Binary {
-                        column: stringify!(#id_name),
+                        column: #id_columns,
                         value: Box::new(t),
                         operator: ergol::query::Operator::Eq,
                     }
@@ -433,7 +1820,7 @@ pub fn to_table(
                 /// passed as parameter.
                 pub fn neq<T: ergol::tokio_postgres::types::ToSql + Sync + Send + 'static>(t: T) -> ergol::query::Filter {
                     ergol::query::Filter::Binary {
-                        column: stringify!(#id_name),
+                        column: #id_columns,
                         value: Box::new(t),
                         operator: ergol::query::Operator::Neq,
                     }
@@ -443,7 +1830,7 @@ pub fn to_table(
                 /// passed as parameter.
                 pub fn leq<T: ergol::tokio_postgres::types::ToSql + Sync + Send + 'static>(t: T) -> ergol::query::Filter {
                     ergol::query::Filter::Binary {
-                        column: stringify!(#id_name),
+                        column: #id_columns,
                         value: Box::new(t),
                         operator: ergol::query::Operator::Leq,
                     }
@@ -453,7 +1840,7 @@ pub fn to_table(
                 /// passed as parameter.
                 pub fn geq<T: ergol::tokio_postgres::types::ToSql + Sync + Send + 'static>(t: T) -> ergol::query::Filter {
                     ergol::query::Filter::Binary {
-                        column: stringify!(#id_name),
+                        column: #id_columns,
                         value: Box::new(t),
                         operator: ergol::query::Operator::Geq,
                     }
@@ -463,7 +1850,7 @@ pub fn to_table(
                 /// as parameter.
                 pub fn lt<T: ergol::tokio_postgres::types::ToSql + Sync + Send + 'static>(t: T) -> ergol::query::Filter {
                     ergol::query::Filter::Binary {
-                        column: stringify!(#id_name),
+                        column: #id_columns,
                         value: Box::new(t),
                         operator: ergol::query::Operator::Lt,
                     }
@@ -473,7 +1860,7 @@ pub fn to_table(
                 /// as parameter.
                 pub fn gt<T: ergol::tokio_postgres::types::ToSql + Sync + Send + 'static>(t: T) -> ergol::query::Filter {
                     ergol::query::Filter::Binary {
-                        column: stringify!(#id_name),
+                        column: #id_columns,
                         value: Box::new(t),
                         operator: ergol::query::Operator::Gt,
                     }
@@ -482,32 +1869,36 @@ pub fn to_table(
                 /// Sorts the the results according to one column in ascending order.
                 pub fn ascend() -> ergol::query::OrderBy {
                     ergol::query::OrderBy {
-                        column: stringify!(#id_name),
+                        column: #id_columns,
                         order: ergol::query::Order::Ascend,
+                        rank: None,
                     }
                 }
 
                 /// Sorts the the results according to one column in descending order.
                 pub fn descend() -> ergol::query::OrderBy {
                     ergol::query::OrderBy {
-                        column: stringify!(#id_name),
+                        column: #id_columns,
                         order: ergol::query::Order::Descend,
+                        rank: None,
                     }
                 }
-
-
             }
+            )*
 
             #(
 
                 /// Module that contains the helpers for the column.
                 pub mod #field_names2 {
 
+                    /// The name of the column.
+                    pub const COLUMN: &str = #field_columns;
+
                     /// Keeps only the results for which the column equals the value passed as
                     /// parameter.
                     pub fn eq<T: ergol::tokio_postgres::types::ToSql + Sync + Send + 'static>(t: T) -> ergol::query::Filter {
                         ergol::query::Filter::Binary {
-                            column: stringify!(#field_names2),
+                            column: #field_columns,
                             value: Box::new(t),
                             operator: ergol::query::Operator::Eq,
                         }
@@ -517,7 +1908,7 @@ pub fn to_table(
                     /// passed as parameter.
                     pub fn neq<T: ergol::tokio_postgres::types::ToSql + Sync + Send + 'static>(t: T) -> ergol::query::Filter {
                         ergol::query::Filter::Binary {
-                            column: stringify!(#field_names2),
+                            column: #field_columns,
                             value: Box::new(t),
                             operator: ergol::query::Operator::Neq,
                         }
@@ -527,7 +1918,7 @@ pub fn to_table(
                     /// passed as parameter.
                     pub fn leq<T: ergol::tokio_postgres::types::ToSql + Sync + Send + 'static>(t: T) -> ergol::query::Filter {
                         ergol::query::Filter::Binary {
-                            column: stringify!(#field_names2),
+                            column: #field_columns,
                             value: Box::new(t),
                             operator: ergol::query::Operator::Leq,
                         }
@@ -537,7 +1928,7 @@ pub fn to_table(
                     /// passed as parameter.
                     pub fn geq<T: ergol::tokio_postgres::types::ToSql + Sync + Send + 'static>(t: T) -> ergol::query::Filter {
                         ergol::query::Filter::Binary {
-                            column: stringify!(#field_names2),
+                            column: #field_columns,
                             value: Box::new(t),
                             operator: ergol::query::Operator::Geq,
                         }
@@ -547,7 +1938,7 @@ pub fn to_table(
                     /// as parameter.
                     pub fn lt<T: ergol::tokio_postgres::types::ToSql + Sync + Send + 'static>(t: T) -> ergol::query::Filter {
                         ergol::query::Filter::Binary {
-                            column: stringify!(#field_names2),
+                            column: #field_columns,
                             value: Box::new(t),
                             operator: ergol::query::Operator::Lt,
                         }
@@ -557,7 +1948,7 @@ pub fn to_table(
                     /// as parameter.
                     pub fn gt<T: ergol::tokio_postgres::types::ToSql + Sync + Send + 'static>(t: T) -> ergol::query::Filter {
                         ergol::query::Filter::Binary {
-                            column: stringify!(#field_names2),
+                            column: #field_columns,
                             value: Box::new(t),
                             operator: ergol::query::Operator::Gt,
                         }
@@ -566,21 +1957,33 @@ pub fn to_table(
                     /// Sorts the the results according to one column in ascending order.
                     pub fn ascend() -> ergol::query::OrderBy {
                         ergol::query::OrderBy {
-                            column: stringify!(#field_names2),
+                            column: #field_columns,
                             order: ergol::query::Order::Ascend,
+                            rank: None,
                         }
                     }
 
                     /// Sorts the the results according to one column in descending order.
                     pub fn descend() -> ergol::query::OrderBy {
                         ergol::query::OrderBy {
-                            column: stringify!(#field_names2),
+                            column: #field_columns,
                             order: ergol::query::Order::Descend,
+                            rank: None,
                         }
                     }
 
                     #field_likes
 
+                    #field_tags
+
+                    #field_arrays
+
+                    #field_ranges
+
+                    #field_fulltexts
+
+                    #field_jsons
+
                 }
             )*
         }
@@ -589,84 +1992,235 @@ pub fn to_table(
     tokens
 }
 
+/// Returns the condition that matches every column of the primary key, whose values are the
+/// parameters starting at `$first`.
+pub fn key_condition(ids: &[&Field], first: usize) -> String {
+    ids.iter()
+        .enumerate()
+        .map(|(i, id)| format!("\"{}\" = ${}", column_name(id), first + i))
+        .collect::<Vec<_>>()
+        .join(" AND ")
+}
+
 /// Generates some helper functions for the type.
-pub fn to_impl(name: &Ident, id_field: &Field, other_fields: &[&Field]) -> TokenStream2 {
-    let id_name = id_field.ident.as_ref().unwrap();
+pub fn to_impl(
+    name: &Ident,
+    table_name: &TableName,
+    ids: &[&Field],
+    other_fields: &[&Field],
+    soft_delete: Option<&Field>,
+) -> TokenStream2 {
+    let id_names = ids
+        .iter()
+        .map(|id| id.ident.as_ref().unwrap())
+        .collect::<Vec<_>>();
 
-    use case::CaseExt;
-    let table_name = format_ident!("{}s", name.to_string().to_snake());
     let queryable = quote! { ergol::Queryable<impl ergol::tokio_postgres::GenericClient> };
-    let error = quote! { ergol::tokio_postgres::Error };
+    let error = quote! { ergol::Error };
 
     let without_id = format_ident!("{}WithoutId", name);
 
-    let field_comment = other_fields
+    // The timestamps are managed by the database, so they are not part of the inserts and updates.
+    let updated_at = other_fields
+        .iter()
+        .find(|field| find_attribute(field, "updated_at").is_some());
+
+    let timestamp_types = other_fields
+        .iter()
+        .filter(|field| is_timestamp(field))
+        .map(|field| &field.ty);
+
+    // The soft delete column is only managed by the delete and restore functions.
+    let other_fields = other_fields
+        .iter()
+        .filter(|field| !is_timestamp(field) && find_attribute(field, "soft_delete").is_none())
+        .copied()
+        .collect::<Vec<_>>();
+
+    // Keys that are not generated by the database must be given when inserting a new element.
+    let inserted_fields = match ids {
+        [id] if Key::from_field(id).is_generated() => other_fields.clone(),
+        _ => ids.iter().chain(other_fields.iter()).copied().collect(),
+    };
+
+    let field_comment = inserted_fields
         .iter()
         .map(|field| find_attribute(field, "doc"))
         .collect::<Vec<_>>();
 
-    let names = other_fields.iter().map(|field| &field.ident);
+    let names = inserted_fields.iter().map(|field| &field.ident);
     let names2 = names.clone();
     let names3 = names.clone();
     let names4 = names.clone();
-    let names5 = names.clone();
+    let names5 = other_fields.iter().map(|field| &field.ident);
 
-    let names_as_strings = names
-        .clone()
-        .map(|x| format!("\"{}\"", x.as_ref().unwrap().to_string()))
+    let names_as_strings = inserted_fields
+        .iter()
+        .map(|x| format!("\"{}\"", column_name(x)))
         .collect::<Vec<_>>()
         .join(", ");
 
-    let original_types = other_fields.iter().map(|field| &field.ty);
+    let original_types = inserted_fields.iter().map(|field| &field.ty);
 
-    let intos = other_fields.iter().map(|field| {
-        let ty = &field.ty;
-        quote! { Into<#ty> }
-    });
+    // Optional relations are given as options of references, e.g. `Some(&user)` or `None`, and the
+    // other fields are converted to the type in the struct.
+    let mut generics = vec![];
+    let mut params = vec![];
+    let mut conversions = vec![];
 
-    let types = other_fields
-        .iter()
-        .enumerate()
-        .map(|(id, _)| format_ident!("T{}", id));
+    for (i, field) in inserted_fields.iter().enumerate() {
+        let ident = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
 
-    let types2 = types.clone();
+        match optional_relation(field) {
+            Some(target) => {
+                params.push(quote! { #ident: Option<&#target> });
+                conversions.push(quote! { #ident.map(ergol::relation::ManyToOne::from) });
+            }
+            None => {
+                let generic = format_ident!("T{}", i);
+                generics.push(quote! { #generic: Into<#ty> });
+                params.push(quote! { #ident: #generic });
+                conversions.push(quote! { #ident.into() });
+            }
+        }
+    }
 
-    let dollars = (1..other_fields.len() + 1)
+    let dollars = (1..inserted_fields.len() + 1)
         .map(|x| format!("${}", x))
         .collect::<Vec<_>>()
         .join(", ");
 
-    let names_and_dollars = names
-        .clone()
+    let mut names_and_dollars = other_fields
+        .iter()
         .enumerate()
-        .map(|(i, name)| format!("\"{}\" = ${}", name.as_ref().unwrap(), i + 1))
-        .collect::<Vec<_>>()
-        .join(", ");
+        .map(|(i, field)| format!("\"{}\" = ${}", column_name(field), i + 1))
+        .collect::<Vec<_>>();
+
+    if let Some(updated_at) = updated_at {
+        names_and_dollars.push(format!("\"{}\" = now()", column_name(updated_at)));
+    }
 
-    let last_dollar = format!("${}", other_fields.len() + 1);
+    let names_and_dollars = names_and_dollars.join(", ");
 
     let insert_query = format!(
-        "INSERT INTO \"{}\"({}) VALUES({}) RETURNING *;",
-        table_name, names_as_strings, dollars,
+        "INSERT INTO {}({}) VALUES({}) RETURNING *;",
+        table_name.qualified(),
+        names_as_strings,
+        dollars,
     );
 
     let update_query = format!(
-        "UPDATE \"{}\" SET {} WHERE \"{}\" = {};",
-        table_name,
+        "UPDATE {} SET {} WHERE {}{};",
+        table_name.qualified(),
         names_and_dollars,
-        id_field.ident.as_ref().unwrap(),
-        last_dollar
+        key_condition(ids, other_fields.len() + 1),
+        match updated_at {
+            Some(updated_at) => format!(" RETURNING \"{}\"", column_name(updated_at)),
+            None => String::new(),
+        }
     );
 
-    let delete_query = format!(
-        "DELETE FROM \"{}\" WHERE \"{}\" = $1;",
-        table_name,
-        id_field.ident.as_ref().unwrap(),
-    );
+    let save = match updated_at {
+        Some(field) => {
+            let updated_at = field.ident.as_ref().unwrap();
+            let updated_at_column = column_name(field);
+            quote! {
+            /// Updates every field of the element in the database, and refreshes its update
+            /// timestamp.
+            ///
+            /// Returns a stale error if the element no longer exists in the database.
+            pub async fn save<Q: #queryable>(&mut self, db: &Q) -> std::result::Result<(), #error> {
+                let row = db.client().query_opt(#update_query, &[ #( &self.#names5, )* #( &self.#id_names, )* ]).await?;
+                let row = row.ok_or(ergol::Error::Stale)?;
+                self.#updated_at = row.try_get(0).map_err(|e| ergol::Error::decode(#updated_at_column, e))?;
+                Ok(())
+            }
+            }
+        }
+        None => quote! {
+            /// Updates every field of the element in the database.
+            ///
+            /// Returns a stale error if the element no longer exists in the database.
+            pub async fn save<Q: #queryable>(&self, db: &Q) -> std::result::Result<(), #error> {
+                let count = db.client().execute(#update_query, &[ #( &self.#names5, )* #( &self.#id_names, )* ]).await?;
+                if count == 0 {
+                    return Err(ergol::Error::Stale);
+                }
+                Ok(())
+            }
+        },
+    };
+
+    let delete = match soft_delete {
+        Some(field) => {
+            let soft_delete = field.ident.as_ref().unwrap();
+            let ty = &field.ty;
+
+            let delete_query = format!(
+                "UPDATE {} SET \"{}\" = now() WHERE {};",
+                table_name.qualified(),
+                column_name(field),
+                key_condition(ids, 1),
+            );
+
+            let restore_query = format!(
+                "UPDATE {} SET \"{}\" = NULL WHERE {};",
+                table_name.qualified(),
+                column_name(field),
+                key_condition(ids, 1),
+            );
+
+            quote! {
+                /// Marks self as deleted in the database.
+                ///
+                /// The element is not removed from the table, and can be fetched again with
+                /// `select().only_deleted()` and restored.
+                pub async fn delete<Q: #queryable>(self, db: &Q) -> std::result::Result<(), #error> {
+                    const _: fn(&#ty) = |x| {
+                        fn assert_timestamp<T: ergol::pg::Timestamp>(_: &Option<T>) {}
+                        assert_timestamp(x);
+                    };
+
+                    db.client().query(#delete_query, &[ #( &self.#id_names, )* ]).await?;
+                    Ok(())
+                }
+
+                /// Restores an element that was marked as deleted.
+                pub async fn restore<Q: #queryable>(&mut self, db: &Q) -> std::result::Result<(), #error> {
+                    db.client().query(#restore_query, &[ #( &self.#id_names, )* ]).await?;
+                    self.#soft_delete = None;
+                    Ok(())
+                }
+            }
+        }
+        None => {
+            let delete_query = format!(
+                "DELETE FROM {} WHERE {};",
+                table_name.qualified(),
+                key_condition(ids, 1),
+            );
+
+            quote! {
+                /// Deletes self from the database.
+                pub async fn delete<Q: #queryable>(self, db: &Q) -> std::result::Result<(), #error> {
+                    db.client().query(#delete_query, &[ #( &self.#id_names, )* ]).await?;
+                    Ok(())
+                }
+            }
+        }
+    };
 
     let without_id_doc = format!("{} is like {}, but without the id.", without_id, name);
 
     quote! {
+        #(
+            const _: fn() = || {
+                fn assert_timestamp<T: ergol::pg::Timestamp>() {}
+                assert_timestamp::<#timestamp_types>();
+            };
+        )*
+
         #[doc=#without_id_doc]
         ///
         /// It is used to insert a new value in the database without specifiying the id, which will
@@ -682,7 +2236,7 @@ pub fn to_impl(name: &Ident, id_field: &Field, other_fields: &[&Field]) -> Token
             /// Inserts the element into the database, returning the real element with its id.
             pub async fn save<Q: #queryable>(self, db: &Q) -> std::result::Result<#name, #error> {
                 let row = db.client().query_one(#insert_query, &[ #( &self.#names4, )* ]).await?;
-                Ok(<#name as ergol::ToTable>::from_row(&row))
+                <#name as ergol::ToTable>::from_row(&row)
             }
         }
 
@@ -692,101 +2246,224 @@ pub fn to_impl(name: &Ident, id_field: &Field, other_fields: &[&Field]) -> Token
             ///
             /// This function tries to convert its inputs to the type in the struct, so you can
             /// easily manage strings for example.
-            pub fn create<#(#types: #intos,)*>(#(#names: #types2, )*) -> #without_id {
+            #[allow(clippy::too_many_arguments)]
+            pub fn create<#(#generics,)*>(#(#params, )*) -> #without_id {
                 #without_id {
                     #(
-                        #names2: #names2.into(),
+                        #names2: #conversions,
                     )*
                 }
             }
 
-            /// Updates every field of the element in the database.
-            pub async fn save<Q: #queryable>(&self, db: &Q) -> std::result::Result<(), #error> {
-                db.client().query(#update_query, &[ #( &self.#names5, )* &self.#id_name ]).await?;
-                Ok(())
-            }
+            #save
 
-            /// Deletes self from the database.
-            pub async fn delete<Q: #queryable>(self, db: &Q) -> std::result::Result<(), #error> {
-                db.client().query(#delete_query, &[&self.id()]).await?;
-                Ok(())
-            }
+            #delete
         }
     }
 }
 
 /// Generates the getters for the unique fields.
-pub fn to_unique(name: &Ident, id_field: &Field, other_fields: &[&Field]) -> TokenStream2 {
-    use case::CaseExt;
-    let table_name = format_ident!("{}s", name.to_string().to_snake());
+pub fn to_unique(
+    name: &Ident,
+    table_name: &TableName,
+    ids: &[&Field],
+    other_fields: &[&Field],
+    composite_fields: &[Vec<&Field>],
+    soft_delete: Option<&Field>,
+) -> TokenStream2 {
+    let getters = std::iter::once(ids.to_vec())
+        .chain(other_fields.iter().map(|field| vec![*field]))
+        .chain(composite_fields.iter().cloned())
+        .map(|fields| to_getter(name, table_name, &fields, soft_delete));
+
+    quote! {
+        impl #name {
+            #(#getters)*
+        }
+    }
+}
 
+/// Generates the getter for a set of fields that is unique.
+pub fn to_getter(
+    name: &Ident,
+    table_name: &TableName,
+    fields: &[&Field],
+    soft_delete: Option<&Field>,
+) -> TokenStream2 {
     let queryable = quote! { ergol::Queryable<impl ergol::tokio_postgres::GenericClient> };
-    let error = quote! { ergol::tokio_postgres::Error };
+    let error = quote! { ergol::Error };
 
-    let fields = &[id_field];
+    let names = fields
+        .iter()
+        .map(|field| field.ident.as_ref().unwrap().to_string())
+        .collect::<Vec<_>>();
 
-    let fields = fields.iter().chain(other_fields.iter());
+    let getter = format_ident!("get_by_{}", names.join("_and_"));
 
-    let getters = fields
-        .clone()
-        .map(|field| format_ident!("get_by_{}", field.ident.as_ref().unwrap()));
+    let query = format!(
+        "SELECT * FROM {} WHERE {}{}",
+        table_name.qualified(),
+        key_condition(fields, 1),
+        not_deleted(table_name, soft_delete),
+    );
 
-    let types = fields.clone().map(|field| &field.ty);
+    let types = fields.iter().map(|field| &field.ty);
 
-    let queries = fields.clone().map(|field| {
-        format!(
-            "SELECT * FROM \"{}\" WHERE \"{}\" = $1",
-            table_name,
-            field.ident.as_ref().unwrap()
-        )
-    });
+    match fields {
+        [field] => {
+            let doc = format!(
+                "Retrieves the {} based on its {} attribute, which is specified as unique in the database.",
+                name,
+                field.ident.as_ref().unwrap()
+            );
 
-    let doc = fields.clone().map(|g| {
-        format!(
-            "Retrieves the {} based on its {} attribute, which is specified as unique in the database.",
-            name,
-            g.ident.as_ref().unwrap()
-        )
-    });
+            quote! {
+                #[doc=#doc]
+                pub async fn #getter<T: Into<#(#types)*>, Q: #queryable>(attr: T, db: &Q) -> std::result::Result<Option<#name>, #error> {
+                    let mut rows = db.client().query(#query, &[&attr.into()]).await?;
+                    rows.pop().map(|x| <#name as ToTable>::from_row(&x)).transpose()
+                }
+            }
+        }
+        _ => {
+            let doc = format!(
+                "Retrieves the {} based on its {} attributes, which are specified as unique in the database.",
+                name,
+                names.join(" and "),
+            );
 
-    quote! {
-        impl #name {
-            #(
+            let idents = fields.iter().map(|field| field.ident.as_ref().unwrap());
+            let idents2 = idents.clone();
+            let generics = (0..fields.len()).map(|i| format_ident!("T{}", i));
+            let generics2 = generics.clone();
+
+            quote! {
                 #[doc=#doc]
-                pub async fn #getters<T: Into<#types>, Q: #queryable>(attr: T, db: &Q) -> std::result::Result<Option<#name>, #error> {
-                    let mut rows = db.client().query(#queries, &[&attr.into()]).await?;
-                    Ok(rows.pop().map(|x| <#name as ToTable>::from_row(&x)))
+                pub async fn #getter<#(#generics: Into<#types>,)* Q: #queryable>(#(#idents: #generics2,)* db: &Q) -> std::result::Result<Option<#name>, #error> {
+                    let mut rows = db.client().query(#query, &[ #( &#idents2.into(), )* ]).await?;
+                    rows.pop().map(|x| <#name as ToTable>::from_row(&x)).transpose()
                 }
-            )*
+            }
         }
     }
 }
 
 /// Struct to help parse the map_by attribute.
-struct MappedBy {
-    pub _paren_token: token::Paren,
-    pub names: Punctuated<Ident, Token![,]>,
+///
+/// The names can be followed by options, e.g. `#[many_to_one(projects, on_delete = "restrict")]`.
+pub struct MappedBy {
+    pub names: Vec<Ident>,
+    pub options: Vec<(Ident, syn::LitStr)>,
 }
 
 impl Parse for MappedBy {
     fn parse(input: ParseStream) -> syn::Result<Self> {
         let content;
+        parenthesized!(content in input);
 
-        Ok(MappedBy {
-            _paren_token: parenthesized!(content in input),
-            names: content.parse_terminated(Ident::parse).unwrap(),
-        })
+        let mut names = vec![];
+        let mut options = vec![];
+
+        while !content.is_empty() {
+            let name: Ident = content.parse()?;
+
+            if content.peek(Token![=]) {
+                content.parse::<Token![=]>()?;
+                options.push((name, content.parse()?));
+            } else {
+                names.push(name);
+            }
+
+            if !content.is_empty() {
+                content.parse::<Token![,]>()?;
+            }
+        }
+
+        Ok(MappedBy { names, options })
+    }
+}
+
+/// Parses the one_to_one, many_to_one or many_to_many attribute of a field, if it has it.
+///
+/// A one to one relation needs exactly one name, a many to one relation at most one, and a many
+/// to many relation at least one.
+pub fn relation_attribute(field: &Field, relation: &str) -> Result<Option<MappedBy>> {
+    let attr = match find_attribute(field, relation) {
+        Some(attr) => attr,
+        None => return Ok(None),
+    };
+
+    if attr.tokens.is_empty() {
+        return Err(Error::new_spanned(
+            attr,
+            format!("expected `#[{}(...)]`", relation),
+        ));
     }
+
+    let m = syn::parse2::<MappedBy>(attr.tokens.clone())?;
+
+    let message = match relation {
+        "one_to_one" if m.names.len() != 1 => "one to one fields must have exactly one map by",
+        "many_to_one" if m.names.len() > 1 => "many to one fields must have at most one map by",
+        "many_to_many" if m.names.is_empty() => {
+            "many to many fields must have at least one attribute"
+        }
+        _ => return Ok(Some(m)),
+    };
+
+    Err(Error::new_spanned(attr, message))
+}
+
+/// Returns the actions given to the one_to_one or many_to_one attribute of a field, for when the
+/// referenced row is deleted and updated.
+pub fn referential_actions(
+    field: &Field,
+) -> Result<(Option<ReferentialAction>, Option<ReferentialAction>)> {
+    let m = match relation_attribute(field, "one_to_one")? {
+        Some(m) => m,
+        None => match relation_attribute(field, "many_to_one")? {
+            Some(m) => m,
+            None => return Ok((None, None)),
+        },
+    };
+
+    let mut actions = (None, None);
+
+    for (key, value) in m.options {
+        let action = ReferentialAction::from_str(&value.value()).map_err(|_| {
+            Error::new_spanned(
+                &value,
+                "unknown action, expected \"cascade\", \"restrict\" or \"set_null\"",
+            )
+        })?;
+
+        match key.to_string().as_str() {
+            "on_delete" => actions.0 = Some(action),
+            "on_update" => actions.1 = Some(action),
+            _ => {
+                return Err(Error::new(
+                    key.span(),
+                    "unknown relation option, expected `on_delete` or `on_update`",
+                ))
+            }
+        }
+    }
+
+    Ok(actions)
 }
 
 /// Changes the types of one to one fields.
-pub fn fix_one_to_one_fields(name: &Ident, fields: &mut FieldsNamed) -> TokenStream2 {
+pub fn fix_one_to_one_fields(
+    name: &Ident,
+    table_name: &TableName,
+    fields: &mut FieldsNamed,
+) -> Result<TokenStream2> {
     use case::CaseExt;
-    let table_name = format_ident!("{}s", name.to_string().to_snake());
     let queryable = quote! { ergol::Queryable<impl ergol::tokio_postgres::GenericClient> };
-    let error = quote! { ergol::tokio_postgres::Error };
+    let error = quote! { ergol::Error };
 
     let fields_clone: FieldsNamed = fields.clone();
+    let not_deleted = not_deleted(table_name, find_soft_delete(&fields_clone));
 
     let mut fields_to_fix = fields
         .named
@@ -803,25 +2480,16 @@ pub fn fix_one_to_one_fields(name: &Ident, fields: &mut FieldsNamed) -> TokenStr
 
     let tokens = fields_clone
         .clone()
-        .map(|x| find_attribute(x, "one_to_one").unwrap())
-        .map(|x| Into::<TokenStream>::into(x.tokens.clone()))
-        .map(|tokens| {
-            let m = parse_macro_input!(tokens as MappedBy);
-            let idents = m.names.into_iter().collect::<Vec<_>>();
-            if idents.len() != 1 {
-                panic!("one to one fields must have exactly one map by");
-            }
-            let name = &idents[0];
-            let q = quote! { #name };
-            q.into()
-        })
-        .map(Into::<TokenStream2>::into);
+        .map(|x| relation_attribute(x, "one_to_one"))
+        .map(|m| Ok(m?.unwrap().names.remove(0)))
+        .collect::<Result<Vec<_>>>()?;
 
     let query = fields_clone.clone().map(|field| {
         format!(
-            "SELECT * FROM \"{}\" WHERE \"{}\" = $1",
-            table_name,
-            field.ident.as_ref().unwrap()
+            "SELECT * FROM {} WHERE \"{}\" = $1{}",
+            table_name.qualified(),
+            column_name(field),
+            not_deleted,
         )
     });
 
@@ -833,7 +2501,7 @@ pub fn fix_one_to_one_fields(name: &Ident, fields: &mut FieldsNamed) -> TokenStr
         )
     });
 
-    let tokens_doc = tokens.clone().zip(types.clone()).map(|(tokens, ty)| {
+    let tokens_doc = tokens.iter().zip(types.clone()).map(|(tokens, ty)| {
         format!(
             "Helper function to retrieve the {} from the {}.",
             quote! { #tokens }.to_string().to_snake(),
@@ -846,15 +2514,15 @@ pub fn fix_one_to_one_fields(name: &Ident, fields: &mut FieldsNamed) -> TokenStr
             impl #name {
                 #[doc=#idents_doc]
                 pub async fn #idents<Q: #queryable>(&self, db: &Q) -> std::result::Result<#types, #error> {
-                    Ok(self.#idents.fetch(db).await?)
+                    self.#idents.fetch(db).await
                 }
             }
 
             impl #types {
                 #[doc=#tokens_doc]
                 pub async fn #tokens<Q: #queryable>(&self, db: &Q) -> std::result::Result<Option<#name>, #error> {
-                    let mut rows = db.client().query(#query, &[&self.id]).await?;
-                    Ok(rows.pop().map(|x| #name::from_row(&x)))
+                    let mut rows = db.client().query(#query, &[&self.id()]).await?;
+                    rows.pop().map(|x| #name::from_row(&x)).transpose()
                 }
             }
         )*
@@ -865,17 +2533,21 @@ pub fn fix_one_to_one_fields(name: &Ident, fields: &mut FieldsNamed) -> TokenStr
         field.ty = syn::Type::Verbatim(quote! { ergol::relation::OneToOne<#ty> });
     }
 
-    q
+    Ok(q)
 }
 
 /// Changes the types of many to one fields.
-pub fn fix_many_to_one_fields(name: &Ident, fields: &mut FieldsNamed) -> TokenStream2 {
+pub fn fix_many_to_one_fields(
+    name: &Ident,
+    table_name: &TableName,
+    fields: &mut FieldsNamed,
+) -> Result<TokenStream2> {
     use case::CaseExt;
-    let table_name = format_ident!("{}s", name.to_string().to_snake());
     let queryable = quote! { ergol::Queryable<impl ergol::tokio_postgres::GenericClient> };
-    let error = quote! { ergol::tokio_postgres::Error };
+    let error = quote! { ergol::Error };
 
     let fields_clone: FieldsNamed = fields.clone();
+    let not_deleted = not_deleted(table_name, find_soft_delete(&fields_clone));
 
     let mut fields_to_fix = fields
         .named
@@ -890,29 +2562,35 @@ pub fn fix_many_to_one_fields(name: &Ident, fields: &mut FieldsNamed) -> TokenSt
     let idents = fields_clone.clone().map(|x| x.ident.as_ref().unwrap());
     let types = fields_clone.clone().map(|x| &x.ty);
 
-    let massive_iter = fields_clone
-        .clone()
-        .map(|x| find_attribute(x, "many_to_one").unwrap())
-        .map(|x| Into::<TokenStream>::into(x.tokens.clone()))
-        .map(|tokens| {
-            let m = parse_macro_input!(tokens as MappedBy);
-            let idents = m.names.into_iter().collect::<Vec<_>>();
-            if idents.len() > 1 {
-                panic!("many to one fields must have at most one map by");
-            }
-            if idents.is_empty() {
-                let q = quote! {};
-                q.into()
-            } else {
-                let name = &idents[0];
-                let q = quote! { #name };
-                q.into()
+    // The reverse helpers are implemented on the referenced type, even for optional relations.
+    let targets = types.clone().map(|ty| option_inner(ty).unwrap_or(ty));
+
+    let fetches = fields_clone.clone().map(|x| {
+        let ident = x.ident.as_ref().unwrap();
+        if option_inner(&x.ty).is_some() {
+            quote! {
+                match &self.#ident {
+                    Some(x) => x.fetch(db).await.map(Some),
+                    None => Ok(None),
+                }
             }
-        })
-        .map(Into::<TokenStream2>::into)
-        .zip(types.clone())
+        } else {
+            quote! { self.#ident.fetch(db).await }
+        }
+    });
+
+    // The reverse helper is only generated when the relation is given a name.
+    let reverses = fields_clone
+        .clone()
+        .map(|x| relation_attribute(x, "many_to_one"))
+        .map(|m| Ok(m?.unwrap().names.pop()))
+        .collect::<Result<Vec<_>>>()?;
+
+    let massive_iter = reverses
+        .iter()
+        .zip(targets)
         .zip(fields_clone.clone())
-        .filter(|((x, _), _)| !x.is_empty());
+        .filter_map(|((x, target), field)| Some(((x.as_ref()?, target), field)));
 
     let tokens = massive_iter.clone().map(|x| x.0 .0);
     let tokens_types = massive_iter.clone().map(|x| x.0 .1);
@@ -920,9 +2598,10 @@ pub fn fix_many_to_one_fields(name: &Ident, fields: &mut FieldsNamed) -> TokenSt
 
     let query = tokens_fields.map(|field| {
         format!(
-            "SELECT * FROM \"{}\" WHERE \"{}\" = $1",
-            table_name,
-            field.ident.as_ref().unwrap()
+            "SELECT * FROM {} WHERE \"{}\" = $1{}",
+            table_name.qualified(),
+            column_name(field),
+            not_deleted,
         )
     });
 
@@ -936,7 +2615,6 @@ pub fn fix_many_to_one_fields(name: &Ident, fields: &mut FieldsNamed) -> TokenSt
 
     let tokens_doc = tokens
         .clone()
-        .into_iter()
         .zip(tokens_types.clone())
         .map(|(tokens, ty)| {
             format!(
@@ -951,7 +2629,7 @@ pub fn fix_many_to_one_fields(name: &Ident, fields: &mut FieldsNamed) -> TokenSt
             impl #name {
                 #[doc=#idents_doc]
                 pub async fn #idents<Q: #queryable>(&self, db: &Q) -> std::result::Result<#types, #error> {
-                    Ok(self.#idents.fetch(db).await?)
+                    #fetches
                 }
             }
         )*
@@ -962,8 +2640,8 @@ pub fn fix_many_to_one_fields(name: &Ident, fields: &mut FieldsNamed) -> TokenSt
             impl #tokens_types {
                 #[doc=#tokens_doc]
                 pub async fn #tokens<Q: #queryable>(&self, db: &Q) -> std::result::Result<Vec<#name>, #error> {
-                    let mut rows = db.client().query(#query, &[&self.id]).await?;
-                    Ok(rows.iter().map(#name::from_row).collect::<Vec<_>>())
+                    let mut rows = db.client().query(#query, &[&self.id()]).await?;
+                    rows.iter().map(#name::from_row).collect()
                 }
             }
         )*
@@ -971,42 +2649,52 @@ pub fn fix_many_to_one_fields(name: &Ident, fields: &mut FieldsNamed) -> TokenSt
 
     for field in &mut fields_to_fix {
         let ty = &field.ty;
-        field.ty = syn::Type::Verbatim(quote! { ergol::relation::ManyToOne<#ty> });
+        field.ty = match option_inner(ty) {
+            Some(inner) => syn::parse_quote! { Option<ergol::relation::ManyToOne<#inner>> },
+            None => syn::Type::Verbatim(quote! { ergol::relation::ManyToOne<#ty> }),
+        };
     }
 
-    quote! {
+    Ok(quote! {
         #q1
         #q2
-    }
+    })
 }
 
 /// Changes the types of many to many fields.
-pub fn fix_many_to_many_fields(name: &Ident, fields: &FieldsNamed) -> TokenStream2 {
+pub fn fix_many_to_many_fields(
+    name: &Ident,
+    table_name: &TableName,
+    fields: &FieldsNamed,
+) -> Result<TokenStream2> {
     use case::CaseExt;
-    let table_name = format_ident!("{}s", name.to_string().to_snake());
     let queryable = quote! { ergol::Queryable<impl ergol::tokio_postgres::GenericClient> };
-    let error = quote! { ergol::tokio_postgres::Error };
+    let error = quote! { ergol::Error };
 
     let fields_to_fix = fields
         .named
         .iter()
         .filter(|field| find_attribute(field, "many_to_many").is_some());
 
-    let extra = fields_to_fix
+    // The first name is the name of the reverse helper, the others are the extra columns.
+    let mapped_by = fields_to_fix
         .clone()
-        .map(|x| find_attribute(x, "many_to_many").unwrap())
-        .map(|x| Into::<TokenStream>::into(x.tokens.clone()))
-        .map(|tokens| {
-            let m = parse::<MappedBy>(tokens).unwrap();
-            m.names.into_iter().skip(1).collect::<Vec<_>>()
-        });
+        .map(|x| relation_attribute(x, "many_to_many").map(Option::unwrap))
+        .collect::<Result<Vec<_>>>()?;
+
+    let extra = mapped_by
+        .iter()
+        .map(|m| m.names.iter().skip(1).cloned().collect::<Vec<_>>());
 
     let count = extra.clone().map(|x| x.len());
 
-    let extra_rows_without_offset = count.clone().map(|count| {
-        (0..count)
-            .map(|i| {
-                quote! { x.get(#i) }
+    let extra_rows_without_offset = extra.clone().map(|extra| {
+        extra
+            .iter()
+            .enumerate()
+            .map(|(i, x)| {
+                let column = x.to_string().to_snake();
+                quote! { x.try_get(#i).map_err(|e| ergol::Error::decode(#column, e))? }
             })
             .collect::<Vec<_>>()
     });
@@ -1031,7 +2719,7 @@ pub fn fix_many_to_many_fields(name: &Ident, fields: &FieldsNamed) -> TokenStrea
     });
 
     let update_names = extra_snake.clone().map(|x| {
-        x.into_iter()
+        x.iter()
             .map(|y| format_ident!("update_{}", y.to_string().to_snake()))
             .collect::<Vec<_>>()
     });
@@ -1048,7 +2736,7 @@ pub fn fix_many_to_many_fields(name: &Ident, fields: &FieldsNamed) -> TokenStrea
         .clone()
         .zip(extra_snake.clone())
         .map(|(x, snake)| {
-            let y = format_ident!("{}_{}_join", table_name, x.ident.as_ref().unwrap()).to_string();
+            let y = table_name.join(x).qualified();
 
             let extra_columns = snake
                 .iter()
@@ -1059,16 +2747,16 @@ pub fn fix_many_to_many_fields(name: &Ident, fields: &FieldsNamed) -> TokenStrea
             let extra_columns = extra_columns.join(",");
 
             let extra_dollars = snake
-                .into_iter()
+                .iter()
                 .enumerate()
                 .map(|(x, _)| format!("${}", x + 3))
                 .collect::<Vec<_>>()
                 .join(",");
 
             format!(
-                "INSERT INTO \"{}\"(\"{}_id\", \"{}_id\" {}) VALUES ($1, $2 {});",
+                "INSERT INTO {}(\"{}_id\", \"{}_id\" {}) VALUES ($1, $2 {});",
                 y,
-                table_name,
+                table_name.name,
                 x.ident.as_ref().unwrap(),
                 if empty {
                     String::new()
@@ -1087,7 +2775,7 @@ pub fn fix_many_to_many_fields(name: &Ident, fields: &FieldsNamed) -> TokenStrea
         .clone()
         .zip(extra_snake.clone())
         .map(|(x, snake)| {
-            let y = format_ident!("{}_{}_join", table_name, x.ident.as_ref().unwrap()).to_string();
+            let y = table_name.join(x).qualified();
 
             let extra_columns = snake.iter().map(|x| x.to_string());
             let extra_dollars = snake.iter().enumerate().map(|(x, _)| format!("${}", x + 3));
@@ -1096,11 +2784,11 @@ pub fn fix_many_to_many_fields(name: &Ident, fields: &FieldsNamed) -> TokenStrea
                 .zip(extra_dollars)
                 .map(|(z, t)| {
                     format!(
-                        "UPDATE \"{}\" SET \"{}\" = {} WHERE \"{}_id\" = $1 AND \"{}_id\" = $2;",
+                        "UPDATE {} SET \"{}\" = {} WHERE \"{}_id\" = $1 AND \"{}_id\" = $2;",
                         y,
                         z,
                         t,
-                        table_name,
+                        table_name.name,
                         x.ident.as_ref().unwrap(),
                     )
                 })
@@ -1108,122 +2796,108 @@ pub fn fix_many_to_many_fields(name: &Ident, fields: &FieldsNamed) -> TokenStrea
         });
 
     let delete_queries = fields_to_fix.clone().map(|x| {
-        let y = format_ident!("{}_{}_join", table_name, x.ident.as_ref().unwrap()).to_string();
+        let y = table_name.join(x).qualified();
         format!(
-            "DELETE FROM \"{}\" WHERE \"{}_id\" = $1 AND \"{}_id\" = $2 RETURNING \"id\";",
+            "DELETE FROM {} WHERE \"{}_id\" = $1 AND \"{}_id\" = $2 RETURNING \"id\";",
             y,
-            table_name,
+            table_name.name,
             x.ident.as_ref().unwrap(),
         )
     });
 
     let types = fields_to_fix.clone().map(|x| &x.ty);
-    let types_names = types
-        .clone()
-        .map(|x| format!("{}s", quote! {#x}.to_string().to_snake()));
 
-    let tokens = fields_to_fix
-        .clone()
-        .map(|x| find_attribute(x, "many_to_many").unwrap())
-        .map(|x| Into::<TokenStream>::into(x.tokens.clone()))
-        .map(|tokens| {
-            let m = parse_macro_input!(tokens as MappedBy);
-            let idents = m.names.into_iter().collect::<Vec<_>>();
-            if idents.len() < 1 {
-                panic!("many to many fields must have at least one attribute");
-            }
-            let name = &idents[0];
-            let q = quote! { #name };
-            q.into()
-        })
-        .map(Into::<TokenStream2>::into);
+    // Without extra columns, the rows are the elements themselves and not tuples.
+    let decode = |ty: TokenStream2, count: usize, extra: Vec<TokenStream2>| {
+        if extra.is_empty() {
+            quote! { #ty::from_row_with_offset(x, #count) }
+        } else {
+            quote! { Ok((#ty::from_row_with_offset(x, #count)? #(, #extra)*)) }
+        }
+    };
 
-    let add_tokens = fields_to_fix
+    let decode_types = types
         .clone()
-        .map(|x| find_attribute(x, "many_to_many").unwrap())
-        .map(|x| Into::<TokenStream>::into(x.tokens.clone()))
-        .map(|tokens| {
-            let m = parse_macro_input!(tokens as MappedBy);
-            let idents = m.names.into_iter().collect::<Vec<_>>();
-            if idents.len() < 1 {
-                panic!("many to many fields must have at least one attribute");
-            }
-            let name = &idents[0];
-            let mut name = format!("add_{}", name.to_string());
-            name.pop();
-            let name = format_ident!("{}", name);
-            let q = quote! { #name };
-            q.into()
-        })
-        .map(Into::<TokenStream2>::into);
+        .zip(count.clone())
+        .zip(extra_rows_without_offset.clone())
+        .map(|((ty, count), extra)| decode(quote! { #ty }, count, extra))
+        .collect::<Vec<_>>();
 
-    let delete_tokens = fields_to_fix
+    let decode_names = count
         .clone()
-        .map(|x| find_attribute(x, "many_to_many").unwrap())
-        .map(|x| Into::<TokenStream>::into(x.tokens.clone()))
-        .map(|tokens| {
-            let m = parse_macro_input!(tokens as MappedBy);
-            let idents = m.names.into_iter().collect::<Vec<_>>();
-            if idents.len() < 1 {
-                panic!("many to many fields must have at least one attribute");
-            }
-            let name = &idents[0];
-            let mut name = format!("remove_{}", name.to_string());
-            name.pop();
-            let name = format_ident!("{}", name);
-            let q = quote! { #name };
-            q.into()
-        })
-        .map(Into::<TokenStream2>::into);
+        .zip(extra_rows_without_offset)
+        .map(|(count, extra)| decode(quote! { #name }, count, extra))
+        .collect::<Vec<_>>();
+
+    let tokens = mapped_by.iter().map(|m| &m.names[0]);
+
+    let add_tokens = tokens.clone().map(|name| {
+        let mut name = format!("add_{}", name);
+        name.pop();
+        format_ident!("{}", name)
+    });
 
+    let delete_tokens = tokens.clone().map(|name| {
+        let mut name = format!("remove_{}", name);
+        name.pop();
+        format_ident!("{}", name)
+    });
+
+    // The name and the primary key of the other table are only known at runtime, so they are left
+    // as the `{0}` and `{1}` parameters of the query.
     let select_queries = fields_to_fix
         .clone()
-        .zip(types_names)
         .zip(extra_snake.clone())
-        .map(|((x, z), extra)| {
-            let y = format_ident!("{}_{}_join", table_name, x.ident.as_ref().unwrap()).to_string();
+        .map(|(x, extra)| {
+            let y = table_name.join(x).qualified();
             let extra_vars = extra
                 .iter()
-                .map(|x| format!("\"{}\".\"{}\"", y, x.to_string()))
+                .map(|x| format!("{}.\"{}\"", y, x))
                 .collect::<Vec<_>>()
                 .join(", ");
 
             format!(
-                "SELECT {} {4}.* FROM \"{}\",\"{4}\" WHERE \"{}_id\" = $1 AND \"{4}\".\"id\" = \"{}_id\";",
+                "SELECT {} {{0}}.* FROM {},{{0}} WHERE \"{}_id\" = $1 AND {{0}}.\"{{1}}\" = \"{}_id\"",
                 if extra.is_empty() {
                     String::new()
                 } else {
                     format!("{}, ", extra_vars)
                 },
                 y,
-                table_name,
+                table_name.name,
                 x.ident.as_ref().unwrap(),
-                z,
             )
         });
 
+    let not_deleted = not_deleted(table_name, find_soft_delete(fields));
+    let id_column = find_id(fields)
+        .map(|(ids, _)| column_name(ids[0]))
+        .unwrap_or_default();
+
     let query = fields_to_fix.zip(extra_snake.clone()).map(|(x, extra)| {
-        let y = format_ident!("{}_{}_join", table_name, x.ident.as_ref().unwrap()).to_string();
+        let y = table_name.join(x).qualified();
         format!(
-            "SELECT {} \"{}\".* FROM \"{}\",\"{}\" WHERE \"{}_id\" = $1 AND \"{}_id\" = \"{}\".\"id\";",
+            "SELECT {} {}.* FROM {},{} WHERE \"{}_id\" = $1 AND \"{}_id\" = {}.\"{}\"{};",
             if extra.is_empty() {
                 String::from("")
             } else {
                 format!(
                     "{}, ",
                     extra
-                        .into_iter()
-                        .map(|x| format!("\"{}\".\"{}\"", y, x.to_string()))
+                        .iter()
+                        .map(|x| format!("{}.\"{}\"", y, x))
                         .collect::<Vec<_>>()
                         .join(", ")
                 )
             },
-            table_name,
+            table_name.qualified(),
             y,
-            table_name,
+            table_name.qualified(),
             x.ident.as_ref().unwrap(),
-            table_name,
-            table_name,
+            table_name.name,
+            table_name.qualified(),
+            id_column,
+            not_deleted,
         )
     });
 
@@ -1245,10 +2919,22 @@ pub fn fix_many_to_many_fields(name: &Ident, fields: &FieldsNamed) -> TokenStrea
 
                 /// TODO fix doc
                 pub async fn #names<Q: #queryable>(&self, db: &Q) -> std::result::Result<Vec<(#types #(, #extra)*)>, #error> {
-                    let rows = db.client().query(#select_queries, &[&self.id()]).await?;
-                    Ok(rows.iter().map(|x| {
-                        (#types::from_row_with_offset(x, #count) #(, #extra_rows_without_offset)*)
-                    }).collect::<Vec<_>>())
+                    let select = format!(
+                        #select_queries,
+                        <#types as ergol::ToTable>::qualified_table_name(),
+                        <#types as ergol::ToTable>::id_name(),
+                    );
+                    let query = match <#types as ergol::ToTable>::soft_delete_name() {
+                        Some(soft_delete) => format!(
+                            "{} AND {}.\"{}\" IS NULL;",
+                            select,
+                            <#types as ergol::ToTable>::qualified_table_name(),
+                            soft_delete,
+                        ),
+                        None => format!("{};", select),
+                    };
+                    let rows = db.client().query(&query as &str, &[&self.id()]).await?;
+                    rows.iter().map(|x| #decode_types).collect()
                 }
 
                 #(
@@ -1263,10 +2949,8 @@ pub fn fix_many_to_many_fields(name: &Ident, fields: &FieldsNamed) -> TokenStrea
             impl #types {
                 /// TODO fix doc
                 pub async fn #tokens<Q: #queryable>(&self, db: &Q) -> std::result::Result<Vec<(#name #(, #extra)*)>, #error> {
-                    let mut rows = db.client().query(#query, &[&self.id()]).await?;
-                    Ok(rows.into_iter().map(|x| {
-                        (#name::from_row_with_offset(&x, #count) #(, #extra_rows_without_offset)*)
-                    }).collect::<Vec<_>>())
+                    let rows = db.client().query(#query, &[&self.id()]).await?;
+                    rows.iter().map(|x| #decode_names).collect()
                 }
 
                 /// TODO fix doc
@@ -1292,5 +2976,5 @@ pub fn fix_many_to_many_fields(name: &Ident, fields: &FieldsNamed) -> TokenStrea
         )*
     };
 
-    q
+    Ok(q)
 }
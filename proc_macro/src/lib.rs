@@ -4,15 +4,48 @@ use syn::{parse_macro_input, DeriveInput};
 
 mod ergol;
 mod pgenum;
+mod pgjson;
+mod pgtype;
+mod sql;
 
 #[proc_macro_attribute]
-pub fn ergol(_attr: TokenStream, input: TokenStream) -> TokenStream {
+pub fn ergol(attr: TokenStream, input: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr as ergol::Args);
     let input = parse_macro_input!(input as DeriveInput);
-    ergol::generate(input)
+    ergol::Options::from_args(args)
+        .and_then(|options| ergol::generate(options, input))
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
 }
 
-#[proc_macro_derive(PgEnum)]
+#[proc_macro_derive(PgEnum, attributes(pg_enum))]
 pub fn derive_pgenum(input: TokenStream) -> TokenStream {
-    let ast = syn::parse(input).unwrap();
+    let ast = parse_macro_input!(input as DeriveInput);
     pgenum::generate(&ast)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+#[proc_macro_derive(PgJson)]
+pub fn derive_pgjson(input: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(input as DeriveInput);
+    pgjson::generate(&ast)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+#[proc_macro_derive(PgType, attributes(pg_type))]
+pub fn derive_pgtype(input: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(input as DeriveInput);
+    pgtype::generate(&ast)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+#[proc_macro]
+pub fn sql(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as sql::Sql);
+    sql::generate(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
 }
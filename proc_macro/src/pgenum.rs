@@ -1,69 +1,140 @@
-use std::fs::{create_dir_all, File};
-use std::io::Write;
-
 use case::CaseExt;
 
-use proc_macro::TokenStream;
-
 use proc_macro2::TokenStream as TokenStream2;
 
-use syn::{self, Ident};
+use syn::{
+    self, Attribute, Error, Fields, Ident, Lit, LitStr, Meta, MetaNameValue, NestedMeta, Result,
+};
 
 use quote::{format_ident, quote};
 
 use ergol_core::{Element, Enum};
 
+use crate::ergol::register_schema;
+
 /// Generates functions and trait implementations for enum types.
-pub fn generate(ast: &syn::DeriveInput) -> TokenStream {
+pub fn generate(ast: &syn::DeriveInput) -> Result<TokenStream2> {
     let name = &ast.ident;
 
     let variants = match &ast.data {
         syn::Data::Enum(syn::DataEnum { variants, .. }) => variants,
-        _ => panic!("Expected enum"),
+        _ => {
+            return Err(Error::new(
+                name.span(),
+                "PgEnum can only be derived for enums",
+            ))
+        }
     };
 
+    if let Some(variant) = variants.iter().find(|x| x.fields != Fields::Unit) {
+        return Err(Error::new_spanned(
+            &variant.fields,
+            "the variants of a PgEnum can not have fields",
+        ));
+    }
+
+    let rename_all = find_option(&ast.attrs, "rename_all")?;
+
+    let labels = variants
+        .iter()
+        .map(|x| match find_option(&x.attrs, "rename")? {
+            Some(rename) => Ok(rename.value()),
+            None => label(&x.ident, rename_all.as_ref()),
+        })
+        .collect::<Result<Vec<_>>>()?;
+
     let variants = variants.iter().map(|x| x.ident.clone()).collect::<Vec<_>>();
 
-    let impl_variants = impl_variants(&name, variants.as_slice());
-    let impl_pg = impl_traits(&name, variants.as_slice());
+    let impl_variants = impl_variants(name, &labels);
+    let impl_pg = impl_traits(name, &variants, &labels);
 
     let json = Element::Enum(Enum {
         name: format!("{}", name).to_snake(),
-        variants: variants
-            .into_iter()
-            .map(|x| format!("{}", x).to_snake())
-            .collect(),
+        variants: labels,
     });
 
-    create_dir_all("migrations/current").unwrap();
-    let mut file = File::create(format!("migrations/current/{}.json", &name)).unwrap();
-    file.write_all(
-        serde_json::to_string_pretty(&vec![json])
-            .unwrap()
-            .as_bytes(),
-    )
-    .unwrap();
+    let register = register_schema(name, &[json]);
 
-    let q = quote! {
+    Ok(quote! {
         #impl_variants
         #impl_pg
+        #register
+    })
+}
+
+/// Returns the value of an option of the `#[pg_enum(...)]` attributes, e.g. `rename = "..."`.
+///
+/// The option is the only one accepted by the attributes.
+pub fn find_option(attrs: &[Attribute], option: &str) -> Result<Option<LitStr>> {
+    let mut value = None;
+
+    for attr in attrs.iter().filter(|x| x.path.is_ident("pg_enum")) {
+        let nested = match attr.parse_meta()? {
+            Meta::List(list) => list.nested,
+            meta => {
+                return Err(Error::new_spanned(
+                    meta,
+                    format!("expected `#[pg_enum({} = \"...\")]`", option),
+                ))
+            }
+        };
+
+        for arg in nested {
+            match arg {
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                    path,
+                    lit: Lit::Str(lit),
+                    ..
+                })) if path.is_ident(option) => value = Some(lit),
+                arg => {
+                    return Err(Error::new_spanned(
+                        arg,
+                        format!("unknown pg_enum option, expected `{} = \"...\"`", option),
+                    ))
+                }
+            }
+        }
+    }
+
+    Ok(value)
+}
+
+/// Returns the label of a variant in the database, following the `rename_all` rule of the enum.
+///
+/// The labels are in snake case by default.
+pub fn label(variant: &Ident, rename_all: Option<&LitStr>) -> Result<String> {
+    let variant = variant.to_string();
+
+    let rule = match rename_all {
+        Some(rule) => rule,
+        None => return Ok(variant.to_snake()),
     };
 
-    q.into()
+    Ok(match rule.value().as_str() {
+        "snake_case" => variant.to_snake(),
+        "SCREAMING_SNAKE_CASE" => variant.to_snake().to_uppercase(),
+        "kebab-case" => variant.to_snake().replace('_', "-"),
+        "camelCase" => variant.to_camel_lowercase(),
+        "PascalCase" => variant,
+        "lowercase" => variant.to_lowercase(),
+        "UPPERCASE" => variant.to_uppercase(),
+        _ => {
+            return Err(Error::new_spanned(
+                rule,
+                "unknown rule, expected \"snake_case\", \"SCREAMING_SNAKE_CASE\", \"kebab-case\", \"camelCase\", \"PascalCase\", \"lowercase\" or \"UPPERCASE\"",
+            ))
+        }
+    })
 }
 
 /// Adds the type_name, create_type and drop_type functions on enum type.
-pub fn impl_variants(name: &Ident, variants: &[Ident]) -> TokenStream2 {
+pub fn impl_variants(name: &Ident, labels: &[String]) -> TokenStream2 {
     let type_name = format_ident!("{}", name.to_string().to_snake());
-    let variants_names = variants
-        .iter()
-        .map(|x| x.to_string().to_snake())
-        .collect::<Vec<_>>();
 
     let create_type = format!(
         "CREATE TYPE {} AS ENUM ('{}');",
         type_name,
-        variants_names.join("', '")
+        labels.join("', '")
     );
 
     let drop_type = format!("DROP TYPE {} CASCADE;", type_name);
@@ -89,19 +160,26 @@ pub fn impl_variants(name: &Ident, variants: &[Ident]) -> TokenStream2 {
 }
 
 /// Adds the implementation of the Pg, ToSql and FromSql traits for enum type.
-pub fn impl_traits(name: &Ident, variants: &[Ident]) -> TokenStream2 {
-    let type_name = format_ident!("{}", name.to_string().to_snake());
-    let type_name = format!("{} NOT NULL", type_name);
-
-    let snake_variants = variants.iter().map(|x| x.to_string().to_snake());
-    let snake_variants2 = snake_variants.clone();
+///
+/// The values are only accepted for the postgres enum type of the same name.
+pub fn impl_traits(name: &Ident, variants: &[Ident], labels: &[String]) -> TokenStream2 {
+    let type_name = name.to_string().to_snake();
+    let ty = format!("{} NOT NULL", type_name);
+
+    let accepts = quote! {
+        fn accepts(ty: &ergol::tokio_postgres::types::Type) -> bool {
+            matches!(ty.kind(), ergol::tokio_postgres::types::Kind::Enum(_)) && ty.name() == #type_name
+        }
+    };
 
     let impl_pg = quote! {
         impl ergol::pg::Pg for #name {
             fn ty() -> String {
-                String::from(#type_name)
+                String::from(#ty)
             }
         }
+
+        impl ergol::pg::Defined for #name {}
     };
 
     let impl_to_sql = quote! {
@@ -116,16 +194,14 @@ pub fn impl_traits(name: &Ident, variants: &[Ident]) -> TokenStream2 {
 
                 let s = match self {
                     #(
-                        #name::#variants => #snake_variants,
+                        #name::#variants => #labels,
                     )*
                 };
                 out.put_slice(s.as_bytes());
                 Ok(ergol::tokio_postgres::types::IsNull::No)
             }
 
-            fn accepts(ty: &ergol::tokio_postgres::types::Type) -> bool {
-                true
-            }
+            #accepts
 
             ergol::tokio_postgres::types::to_sql_checked!();
         }
@@ -138,21 +214,16 @@ pub fn impl_traits(name: &Ident, variants: &[Ident]) -> TokenStream2 {
                 ty: &ergol::tokio_postgres::types::Type,
                 raw: &'a [u8]
             ) -> std::result::Result<Self, Box<dyn std::error::Error + 'static + Sync + Send>> {
-                let s = std::str::from_utf8(raw).unwrap();
-                match s.as_ref() {
+                match std::str::from_utf8(raw)? {
                     #(
-                        #snake_variants2 => Ok(#name::#variants),
+                        #labels => Ok(#name::#variants),
                     )*
-                    _ => unreachable!(),
+                    s => Err(format!("unknown variant {} of the enum {}", s, #type_name).into()),
                 }
             }
 
-            fn accepts(ty: &ergol::tokio_postgres::types::Type) -> bool {
-                true
-            }
+            #accepts
         }
-
-
     };
 
     quote! {
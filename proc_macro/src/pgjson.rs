@@ -0,0 +1,133 @@
+use case::CaseExt;
+
+use proc_macro2::TokenStream as TokenStream2;
+
+use syn::{self, Attribute, Error, Lit, Meta, MetaNameValue, NestedMeta, Result};
+
+use quote::quote;
+
+use ergol_core::{Domain, Element};
+
+use crate::ergol::register_schema;
+
+/// Generates the trait implementations for types stored as json.
+pub fn generate(ast: &syn::DeriveInput) -> Result<TokenStream2> {
+    let name = &ast.ident;
+
+    if !ast.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &ast.generics,
+            "PgJson can not be derived for generic types",
+        ));
+    }
+
+    let type_name = name.to_string().to_snake();
+    let ty = format!("{} NOT NULL", type_name);
+
+    // The values are accepted for the domain of the type, and for any jsonb value.
+    let accepts = quote! {
+        fn accepts(ty: &ergol::tokio_postgres::types::Type) -> bool {
+            *ty == ergol::tokio_postgres::types::Type::JSONB
+                || matches!(ty.kind(), ergol::tokio_postgres::types::Kind::Domain(_)) && ty.name() == #type_name
+        }
+    };
+
+    let tag = match find_tag(&ast.attrs) {
+        Some(tag) => quote! { Some(#tag) },
+        None => quote! { None },
+    };
+
+    let impl_pg = quote! {
+        impl ergol::pg::Pg for #name {
+            fn ty() -> String {
+                String::from(#ty)
+            }
+
+            fn tag() -> Option<&'static str> {
+                #tag
+            }
+        }
+
+        impl ergol::pg::Defined for #name {}
+    };
+
+    // The binary format of jsonb is a version number followed by the json text.
+    let impl_to_sql = quote! {
+        impl ergol::tokio_postgres::types::ToSql for #name {
+            fn to_sql(
+                &self,
+                ty: &ergol::tokio_postgres::types::Type,
+                out: &mut ergol::bytes::BytesMut
+            ) -> std::result::Result<ergol::tokio_postgres::types::IsNull, Box<dyn std::error::Error + 'static + Sync + Send>> {
+
+                use ergol::bytes::BufMut;
+
+                out.put_u8(1);
+                ergol::serde_json::to_writer(out.writer(), self)?;
+                Ok(ergol::tokio_postgres::types::IsNull::No)
+            }
+
+            #accepts
+
+            ergol::tokio_postgres::types::to_sql_checked!();
+        }
+    };
+
+    let impl_from_sql = quote! {
+        impl<'a> ergol::tokio_postgres::types::FromSql<'a> for #name {
+            fn from_sql(
+                ty: &ergol::tokio_postgres::types::Type,
+                raw: &'a [u8]
+            ) -> std::result::Result<Self, Box<dyn std::error::Error + 'static + Sync + Send>> {
+                match raw.split_first() {
+                    Some((1, json)) => Ok(ergol::serde_json::from_slice(json)?),
+                    _ => Err(format!("unsupported jsonb encoding for the type {}", #type_name).into()),
+                }
+            }
+
+            #accepts
+        }
+    };
+
+    let json = Element::Domain(Domain {
+        name: type_name,
+        ty: String::from("JSONB"),
+        check: None,
+    });
+
+    let register = register_schema(name, &[json]);
+
+    Ok(quote! {
+        #impl_pg
+        #impl_to_sql
+        #impl_from_sql
+        #register
+    })
+}
+
+/// Returns the key of the field that holds the name of the variants, given by the
+/// `#[serde(tag = "...")]` attribute.
+pub fn find_tag(attrs: &[Attribute]) -> Option<String> {
+    for attr in attrs.iter().filter(|x| x.path.is_ident("serde")) {
+        // The attributes that are not for ergol are checked by serde.
+        let nested = match attr.parse_meta() {
+            Ok(Meta::List(list)) => list.nested,
+            _ => continue,
+        };
+
+        for arg in nested {
+            if let NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                path,
+                lit: Lit::Str(lit),
+                ..
+            })) = arg
+            {
+                if path.is_ident("tag") {
+                    return Some(lit.value());
+                }
+            }
+        }
+    }
+
+    None
+}
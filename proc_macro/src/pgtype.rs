@@ -0,0 +1,186 @@
+use case::CaseExt;
+
+use proc_macro2::TokenStream as TokenStream2;
+
+use syn::{self, Attribute, Error, Fields, Lit, Meta, MetaNameValue, NestedMeta, Result};
+
+use quote::quote;
+
+use ergol_core::{Alias, Domain, Element, Ty};
+
+use crate::ergol::{assert_defined, column_ty, register_schema};
+
+/// The options of the `#[pg_type(...)]` attributes of a newtype.
+#[derive(Default)]
+pub struct PgTypeOptions {
+    /// Whether the values are stored in a domain.
+    pub domain: bool,
+
+    /// The condition that the values of the domain must satisfy.
+    pub check: Option<String>,
+}
+
+impl PgTypeOptions {
+    /// Reads the options of the `#[pg_type(...)]` attributes.
+    ///
+    /// A check implies that the values are stored in a domain.
+    pub fn from_attrs(attrs: &[Attribute]) -> Result<PgTypeOptions> {
+        let mut options = PgTypeOptions::default();
+
+        for attr in attrs.iter().filter(|x| x.path.is_ident("pg_type")) {
+            let nested = match attr.parse_meta()? {
+                Meta::List(list) => list.nested,
+                meta => {
+                    return Err(Error::new_spanned(
+                        meta,
+                        "expected `#[pg_type(domain)]` or `#[pg_type(check = \"...\")]`",
+                    ))
+                }
+            };
+
+            for arg in nested {
+                match arg {
+                    NestedMeta::Meta(Meta::Path(path)) if path.is_ident("domain") => {
+                        options.domain = true
+                    }
+                    NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                        path,
+                        lit: Lit::Str(value),
+                        ..
+                    })) if path.is_ident("check") => {
+                        options.domain = true;
+                        options.check = Some(value.value());
+                    }
+                    arg => {
+                        return Err(Error::new_spanned(
+                            arg,
+                            "unknown pg_type option, expected `domain` or `check = \"...\"`",
+                        ))
+                    }
+                }
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+/// Generates the trait implementations for newtypes, that delegate to the inner type.
+pub fn generate(ast: &syn::DeriveInput) -> Result<TokenStream2> {
+    let name = &ast.ident;
+
+    let inner = match &ast.data {
+        syn::Data::Struct(syn::DataStruct {
+            fields: Fields::Unnamed(fields),
+            ..
+        }) if fields.unnamed.len() == 1 => &fields.unnamed[0].ty,
+        _ => {
+            return Err(Error::new(
+                name.span(),
+                "PgType can only be derived for structs with a single unnamed field",
+            ))
+        }
+    };
+
+    if !ast.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &ast.generics,
+            "PgType can not be derived for generic types",
+        ));
+    }
+
+    let options = PgTypeOptions::from_attrs(&ast.attrs)?;
+    let type_name = name.to_string().to_snake();
+    let inner_ty = column_ty(inner)?;
+
+    let ty = if options.domain {
+        let ty = format!("{} NOT NULL", type_name);
+        quote! { String::from(#ty) }
+    } else {
+        quote! { <#inner as ergol::pg::Pg>::ty() }
+    };
+
+    let impl_pg = quote! {
+        impl ergol::pg::Pg for #name {
+            fn ty() -> String {
+                #ty
+            }
+
+            fn tag() -> Option<&'static str> {
+                <#inner as ergol::pg::Pg>::tag()
+            }
+        }
+
+        impl ergol::pg::Defined for #name {}
+    };
+
+    // The values of a domain are encoded as the values of its base type.
+    let base = quote! {
+        let ty = match ty.kind() {
+            ergol::tokio_postgres::types::Kind::Domain(base) => base,
+            _ => ty,
+        };
+    };
+
+    let impl_to_sql = quote! {
+        impl ergol::tokio_postgres::types::ToSql for #name {
+            fn to_sql(
+                &self,
+                ty: &ergol::tokio_postgres::types::Type,
+                out: &mut ergol::bytes::BytesMut
+            ) -> std::result::Result<ergol::tokio_postgres::types::IsNull, Box<dyn std::error::Error + 'static + Sync + Send>> {
+                #base
+                <#inner as ergol::tokio_postgres::types::ToSql>::to_sql(&self.0, ty, out)
+            }
+
+            fn accepts(ty: &ergol::tokio_postgres::types::Type) -> bool {
+                #base
+                <#inner as ergol::tokio_postgres::types::ToSql>::accepts(ty)
+            }
+
+            ergol::tokio_postgres::types::to_sql_checked!();
+        }
+    };
+
+    let impl_from_sql = quote! {
+        impl<'a> ergol::tokio_postgres::types::FromSql<'a> for #name {
+            fn from_sql(
+                ty: &ergol::tokio_postgres::types::Type,
+                raw: &'a [u8]
+            ) -> std::result::Result<Self, Box<dyn std::error::Error + 'static + Sync + Send>> {
+                #base
+                <#inner as ergol::tokio_postgres::types::FromSql<'a>>::from_sql(ty, raw).map(#name)
+            }
+
+            fn accepts(ty: &ergol::tokio_postgres::types::Type) -> bool {
+                #base
+                <#inner as ergol::tokio_postgres::types::FromSql<'a>>::accepts(ty)
+            }
+        }
+    };
+
+    // The columns of a newtype are resolved to the type of the domain, or to the inner type.
+    let json = if options.domain {
+        Element::Domain(Domain {
+            name: type_name,
+            ty: Ty::Option(Box::new(inner_ty)).to_postgres(),
+            check: options.check,
+        })
+    } else {
+        Element::Alias(Alias {
+            name: type_name,
+            ty: inner_ty,
+        })
+    };
+
+    let register = register_schema(name, &[json]);
+    let defined = assert_defined(inner);
+
+    Ok(quote! {
+        #impl_pg
+        #impl_to_sql
+        #impl_from_sql
+        #defined
+        #register
+    })
+}
@@ -0,0 +1,249 @@
+use case::CaseExt;
+
+use proc_macro2::TokenStream as TokenStream2;
+
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{Error, Expr, Ident, LitStr, Result, Token, Type};
+
+use quote::{format_ident, quote};
+
+/// An argument of the `sql!` macro, that gives the value of a named placeholder.
+pub struct Argument {
+    /// The name of the placeholder.
+    pub name: Ident,
+
+    /// The value of the placeholder.
+    pub value: Expr,
+}
+
+impl Parse for Argument {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let name = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let value = input.parse()?;
+        Ok(Argument { name, value })
+    }
+}
+
+/// The input of the `sql!` macro: the type of the rows, the query and the values of its
+/// placeholders.
+pub struct Sql {
+    /// The type of the rows, which is a struct of a table or a tuple of those structs.
+    pub ty: Type,
+
+    /// The query, with its named placeholders and its columns.
+    pub query: LitStr,
+
+    /// The values of the placeholders.
+    pub arguments: Vec<Argument>,
+}
+
+impl Parse for Sql {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let ty = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let query = input.parse()?;
+
+        let arguments = if input.is_empty() {
+            vec![]
+        } else {
+            input.parse::<Token![,]>()?;
+            Punctuated::<Argument, Token![,]>::parse_terminated(input)?
+                .into_iter()
+                .collect()
+        };
+
+        Ok(Sql {
+            ty,
+            query,
+            arguments,
+        })
+    }
+}
+
+/// Returns the structs of the rows and the paths of the modules of their columns.
+fn entities(ty: &Type) -> Result<Vec<(&Type, TokenStream2)>> {
+    let types = match ty {
+        Type::Tuple(tuple) => tuple.elems.iter().collect(),
+        ty => vec![ty],
+    };
+
+    types
+        .into_iter()
+        .map(|ty| match ty {
+            Type::Path(path) if path.qself.is_none() => {
+                let mut path = path.path.clone();
+                let last = path.segments.last_mut().unwrap();
+                last.ident = format_ident!("{}", last.ident.to_string().to_snake());
+                Ok((ty, quote! { #path }))
+            }
+            ty => Err(Error::new_spanned(
+                ty,
+                "expected a struct of a table or a tuple of structs of tables",
+            )),
+        })
+        .collect()
+}
+
+/// Returns the name of the last segment of the path of a type.
+fn type_name(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Path(path) => path.path.segments.last().map(|x| x.ident.to_string()),
+        _ => None,
+    }
+}
+
+/// Generates the raw query of the `sql!` macro.
+///
+/// The named placeholders of the query, e.g. `:age`, are replaced by positional parameters, and
+/// the columns, e.g. `{age}` or `{User.age}`, by their quoted names. The placeholders and the
+/// arguments must match, and the columns are checked by the compiler against the modules of
+/// columns generated by the `#[ergol]` macro.
+pub fn generate(sql: Sql) -> Result<TokenStream2> {
+    let entities = entities(&sql.ty)?;
+    let template = sql.query.value();
+    let span = sql.query.span();
+
+    let mut pieces: Vec<TokenStream2> = vec![];
+    let mut literal = String::new();
+    let mut placeholders: Vec<String> = vec![];
+    let mut quote_char = None;
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        // The quoted strings and identifiers of the query are kept as they are.
+        if let Some(q) = quote_char {
+            if c == q {
+                quote_char = None;
+            }
+            literal.push(c);
+            continue;
+        }
+
+        match c {
+            '\'' | '"' => {
+                quote_char = Some(c);
+                literal.push(c);
+            }
+            ':' if chars.peek() == Some(&':') => {
+                chars.next();
+                literal.push_str("::");
+            }
+            ':' if chars.peek().is_some_and(|x| x.is_alphabetic() || *x == '_') => {
+                let mut name = String::new();
+                while let Some(&x) = chars.peek() {
+                    if !x.is_alphanumeric() && x != '_' {
+                        break;
+                    }
+                    name.push(x);
+                    chars.next();
+                }
+
+                let index = match placeholders.iter().position(|x| *x == name) {
+                    Some(index) => index,
+                    None => {
+                        placeholders.push(name);
+                        placeholders.len() - 1
+                    }
+                };
+
+                literal.push_str(&format!("${}", index + 1));
+            }
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                literal.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                literal.push('}');
+            }
+            '{' => {
+                let mut column = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(x) => column.push(x),
+                        None => return Err(Error::new(span, "unclosed `{` in the query")),
+                    }
+                }
+
+                // The column is either a field of the first struct, or a field of a struct given
+                // by its name and qualified with its table.
+                let (module, field, table) = match column.trim().split_once('.') {
+                    Some((ty, field)) => {
+                        let (entity, module) = entities
+                            .iter()
+                            .find(|(x, _)| type_name(x).as_deref() == Some(ty.trim()))
+                            .ok_or_else(|| {
+                                Error::new(span, format!("unknown struct `{}` in the query", ty))
+                            })?;
+                        (module, field.trim(), Some(entity))
+                    }
+                    None => (&entities[0].1, column.trim(), None),
+                };
+
+                let mut field = syn::parse_str::<Ident>(field).map_err(|_| {
+                    Error::new(
+                        span,
+                        format!("invalid column `{{{}}}` in the query", column),
+                    )
+                })?;
+                field.set_span(span);
+
+                pieces.push(quote! { #literal });
+                literal.clear();
+
+                if let Some(table) = table {
+                    pieces.push(quote! {
+                        <#table as ergol::ToTable>::qualified_table_name().as_str()
+                    });
+                    pieces.push(quote! { "." });
+                }
+
+                pieces.push(quote! { "\"" });
+                pieces.push(quote! { #module::#field::COLUMN });
+                pieces.push(quote! { "\"" });
+            }
+            c => literal.push(c),
+        }
+    }
+
+    pieces.push(quote! { #literal });
+
+    for (i, argument) in sql.arguments.iter().enumerate() {
+        if sql.arguments[..i].iter().any(|x| x.name == argument.name) {
+            return Err(Error::new_spanned(
+                &argument.name,
+                format!("duplicate argument `{}`", argument.name),
+            ));
+        }
+
+        if !placeholders.contains(&argument.name.to_string()) {
+            return Err(Error::new_spanned(
+                &argument.name,
+                format!("the query has no placeholder `:{}`", argument.name),
+            ));
+        }
+    }
+
+    let values = placeholders
+        .iter()
+        .map(|name| {
+            sql.arguments
+                .iter()
+                .find(|x| x.name == name)
+                .map(|x| &x.value)
+                .ok_or_else(|| Error::new(span, format!("missing argument for `:{}`", name)))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let ty = &sql.ty;
+
+    Ok(quote! {
+        ergol::raw::<#ty>(
+            &[#(#pieces),*].concat(),
+            &[#(&#values as &(dyn ergol::tokio_postgres::types::ToSql + Sync)),*],
+        )
+    })
+}